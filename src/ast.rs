@@ -0,0 +1,390 @@
+use std::fmt;
+use std::rc::Rc;
+
+use super::span::Span;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    String,
+    Int,
+    Float,
+    /// An int or a float.
+    Number,
+    Bool,
+    Function {
+        params: Vec<Type>,
+        return_type: Option<Box<Type>>,
+    },
+    /// The inner type or `none`, written `number?`.
+    Optional(Box<Type>),
+    List(Box<Type>),
+    /// The types of the keys and of the values.
+    Map(Box<Type>, Box<Type>),
+    /// A struct or enum, by the name it was declared with.
+    Named(String),
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::String => write!(f, "String"),
+            Type::Int => write!(f, "Int"),
+            Type::Float => write!(f, "Float"),
+            Type::Number => write!(f, "Number"),
+            Type::Bool => write!(f, "Bool"),
+            Type::Function {
+                params,
+                return_type,
+            } => {
+                write!(f, "fn(")?;
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", param)?;
+                }
+                write!(f, ")")?;
+                if let Some(return_type) = return_type {
+                    write!(f, ": {}", return_type)?;
+                }
+                Ok(())
+            }
+            Type::Optional(type_) => write!(f, "{}?", type_),
+            Type::List(type_) => write!(f, "List<{}>", type_),
+            Type::Map(key, value) => write!(f, "Map<{}, {}>", key, value),
+            Type::Named(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Negate,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogicalOp {
+    And,
+    Or,
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UnaryOp::Negate => write!(f, "-"),
+            UnaryOp::Not => write!(f, "not"),
+        }
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Modulo => "%",
+            BinaryOp::Equal => "==",
+            BinaryOp::NotEqual => "!=",
+            BinaryOp::Less => "<",
+            BinaryOp::LessEqual => "<=",
+            BinaryOp::Greater => ">",
+            BinaryOp::GreaterEqual => ">=",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+impl fmt::Display for LogicalOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LogicalOp::And => write!(f, "and"),
+            LogicalOp::Or => write!(f, "or"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Int(i64),
+    Float(f64),
+    String(String),
+    /// A string with embedded expressions, whose parts are joined: string
+    /// literals for the text and the expressions in between.
+    Interpolation(Vec<Expr>),
+    Bool(bool),
+    None,
+    Variable {
+        name: String,
+        /// How many scopes up the variable is declared, filled in by the
+        /// resolver. `None` means it is a global.
+        depth: Option<usize>,
+    },
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expr>,
+    },
+    Binary {
+        left: Box<Expr>,
+        op: BinaryOp,
+        right: Box<Expr>,
+    },
+    Logical {
+        left: Box<Expr>,
+        op: LogicalOp,
+        right: Box<Expr>,
+    },
+    Call {
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    Function(Rc<Function>),
+    /// `Name { field: value }`
+    Struct {
+        name: String,
+        /// See `ExprKind::Variable::depth`.
+        depth: Option<usize>,
+        fields: Vec<FieldValue>,
+    },
+    Field {
+        target: Box<Expr>,
+        name: String,
+    },
+    List(Vec<Expr>),
+    /// Keys and values, in the order they are written.
+    Map(Vec<(Expr, Expr)>),
+    /// Indexes a list with a number or looks up a key of a map.
+    Index {
+        target: Box<Expr>,
+        index: Box<Expr>,
+    },
+    /// `target[start..end]`, where a missing bound means the start or end of
+    /// the list.
+    Slice {
+        target: Box<Expr>,
+        start: Option<Box<Expr>>,
+        end: Option<Box<Expr>>,
+        inclusive: bool,
+    },
+}
+
+/// A field of a struct literal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldValue {
+    pub name: String,
+    pub value: Expr,
+    /// The span of the field name.
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub type_: Type,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Struct {
+    pub name: String,
+    pub fields: Vec<Field>,
+    /// The span of the struct up to its fields.
+    pub span: Span,
+}
+
+impl Struct {
+    pub fn field(&self, name: &str) -> Option<(usize, &Field)> {
+        self.fields
+            .iter()
+            .enumerate()
+            .find(|(_, field)| field.name == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub name: String,
+    /// The types of the values it carries, empty if it carries none.
+    pub types: Vec<Type>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Enum {
+    pub name: String,
+    pub variants: Vec<Variant>,
+    /// The span of the enum up to its variants.
+    pub span: Span,
+}
+
+impl Enum {
+    pub fn variant(&self, name: &str) -> Option<(usize, &Variant)> {
+        self.variants
+            .iter()
+            .enumerate()
+            .find(|(_, variant)| variant.name == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// `_`, which matches anything.
+    Wildcard,
+    /// `Name` or `Name(a, b)`, binding the values of the variant to the
+    /// names given. `None` for a value left out with `_`.
+    Variant {
+        name: String,
+        bindings: Vec<Option<String>>,
+    },
+}
+
+/// `pattern if guard => body` in a `match`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Vec<Stmt>,
+    /// The span of the pattern and guard.
+    pub span: Span,
+}
+
+impl Arm {
+    /// The names bound by the pattern.
+    pub fn bindings(&self) -> impl Iterator<Item = &String> {
+        let bindings = match &self.pattern {
+            Pattern::Wildcard => &[][..],
+            Pattern::Variant { bindings, .. } => bindings,
+        };
+        bindings.iter().flatten()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: String,
+    pub type_: Option<Type>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    /// `None` for anonymous function expressions.
+    pub name: Option<String>,
+    pub params: Vec<Param>,
+    pub return_type: Option<Type>,
+    pub body: Vec<Stmt>,
+    /// The span of the function up to its body.
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Iterable {
+    Range {
+        start: Expr,
+        end: Expr,
+        inclusive: bool,
+        step: Option<Box<Expr>>,
+    },
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind {
+    Let {
+        name: String,
+        type_: Option<Type>,
+        value: Option<Expr>,
+    },
+    Assign {
+        name: String,
+        value: Expr,
+        /// See `ExprKind::Variable::depth`.
+        depth: Option<usize>,
+    },
+    /// `target[index] = value;`
+    SetIndex {
+        target: Expr,
+        index: Expr,
+        value: Expr,
+    },
+    /// `target.name = value;`
+    SetField {
+        target: Expr,
+        name: String,
+        value: Expr,
+    },
+    Print(Expr),
+    Expression(Expr),
+    Function(Rc<Function>),
+    Struct(Rc<Struct>),
+    Enum(Rc<Enum>),
+    Return(Option<Expr>),
+    Block(Vec<Stmt>),
+    If {
+        condition: Expr,
+        then_branch: Vec<Stmt>,
+        else_branch: Option<Box<Stmt>>,
+    },
+    While {
+        condition: Expr,
+        body: Vec<Stmt>,
+    },
+    /// `for variable in iterable` or `for key, variable in iterable`, where
+    /// the key is the index for lists and strings. Looping over a map with
+    /// one variable gives its keys.
+    For {
+        key: Option<String>,
+        variable: String,
+        iterable: Iterable,
+        body: Vec<Stmt>,
+    },
+    /// Runs the first arm whose pattern matches `value` and whose guard, if
+    /// any, is true.
+    Match {
+        value: Expr,
+        arms: Vec<Arm>,
+    },
+    Break,
+    Continue,
+}
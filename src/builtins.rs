@@ -0,0 +1,767 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use super::error::{FunError, RuntimeError, TypeError};
+use super::map::Map;
+use super::span::Span;
+use super::value::{self, Value};
+
+mod math;
+
+/// A function that is defined in every program and implemented in Rust.
+pub struct Builtin {
+    pub name: &'static str,
+    pub params: &'static [&'static str],
+    function: fn(&Args) -> Result<Value, FunError>,
+}
+
+impl fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Builtin({:?})", self.name)
+    }
+}
+
+pub static BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "len",
+        params: &["value"],
+        function: len,
+    },
+    Builtin {
+        name: "push",
+        params: &["list", "value"],
+        function: push,
+    },
+    Builtin {
+        name: "pop",
+        params: &["list"],
+        function: pop,
+    },
+    Builtin {
+        name: "insert",
+        params: &["list", "index", "value"],
+        function: insert,
+    },
+    Builtin {
+        name: "remove",
+        params: &["collection", "key"],
+        function: remove,
+    },
+    Builtin {
+        name: "get",
+        params: &["map", "key"],
+        function: get_value,
+    },
+    Builtin {
+        name: "keys",
+        params: &["map"],
+        function: keys,
+    },
+    Builtin {
+        name: "values",
+        params: &["map"],
+        function: values,
+    },
+    Builtin {
+        name: "contains",
+        params: &["collection", "value"],
+        function: contains,
+    },
+    Builtin {
+        name: "upper",
+        params: &["string"],
+        function: upper,
+    },
+    Builtin {
+        name: "lower",
+        params: &["string"],
+        function: lower,
+    },
+    Builtin {
+        name: "trim",
+        params: &["string"],
+        function: trim,
+    },
+    Builtin {
+        name: "chars",
+        params: &["string"],
+        function: chars,
+    },
+    Builtin {
+        name: "split",
+        params: &["string", "separator"],
+        function: split,
+    },
+    Builtin {
+        name: "join",
+        params: &["list", "separator"],
+        function: join,
+    },
+    Builtin {
+        name: "replace",
+        params: &["string", "old", "new"],
+        function: replace,
+    },
+    Builtin {
+        name: "starts_with",
+        params: &["string", "prefix"],
+        function: starts_with,
+    },
+    Builtin {
+        name: "ends_with",
+        params: &["string", "suffix"],
+        function: ends_with,
+    },
+    Builtin {
+        name: "find",
+        params: &["string", "part"],
+        function: find,
+    },
+    Builtin {
+        name: "substring",
+        params: &["string", "start", "end"],
+        function: substring,
+    },
+    Builtin {
+        name: "repeat",
+        params: &["string", "times"],
+        function: repeat,
+    },
+    Builtin {
+        name: "to_int",
+        params: &["value"],
+        function: to_int,
+    },
+    Builtin {
+        name: "to_float",
+        params: &["value"],
+        function: to_float,
+    },
+];
+
+/// A namespace of constants and builtins, such as `math`, whose members are
+/// used with `.` like `math.pi`.
+pub struct Module {
+    pub name: &'static str,
+    pub constants: &'static [(&'static str, f64)],
+    /// Named with the module in front, like `math.sqrt`.
+    pub functions: &'static [Builtin],
+}
+
+impl fmt::Debug for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Module({:?})", self.name)
+    }
+}
+
+impl Module {
+    pub fn member(&'static self, name: &str) -> Option<Value> {
+        if let Some((_, constant)) = self
+            .constants
+            .iter()
+            .find(|(constant, _)| *constant == name)
+        {
+            return Some(Value::Float(*constant));
+        }
+        self.functions
+            .iter()
+            .find(|builtin| builtin.name.split_once('.').map(|(_, member)| member) == Some(name))
+            .map(Value::Builtin)
+    }
+}
+
+pub static MODULES: &[Module] = &[math::MODULE];
+
+/// The builtin with this name, which for builtins in modules starts with
+/// the module.
+pub fn get(name: &str) -> Option<&'static Builtin> {
+    BUILTINS
+        .iter()
+        .chain(MODULES.iter().flat_map(|module| module.functions))
+        .find(|builtin| builtin.name == name)
+}
+
+pub fn module(name: &str) -> Option<&'static Module> {
+    MODULES.iter().find(|module| module.name == name)
+}
+
+impl Builtin {
+    /// Calls the builtin with already evaluated arguments. `spans` are the
+    /// spans of the arguments and `span` the span of the call expression.
+    pub fn call(
+        &'static self,
+        values: Vec<Value>,
+        spans: Vec<&Span>,
+        span: &Span,
+    ) -> Result<Value, FunError> {
+        if values.len() != self.params.len() {
+            return Err(FunError::new(
+                TypeError::ArityMismatch {
+                    function: self.name.to_string(),
+                    expected: self.params.len(),
+                    found: values.len(),
+                },
+                span,
+            ));
+        }
+        (self.function)(&Args {
+            builtin: self,
+            values,
+            spans,
+        })
+    }
+}
+
+/// The arguments of a builtin call.
+struct Args<'a> {
+    builtin: &'static Builtin,
+    values: Vec<Value>,
+    spans: Vec<&'a Span>,
+}
+
+impl Args<'_> {
+    fn mismatch(&self, i: usize, expected: &str) -> FunError {
+        FunError::new(
+            TypeError::ArgumentMismatch {
+                function: self.builtin.name.to_string(),
+                param: self.builtin.params[i].to_string(),
+                expected: expected.to_string(),
+                found: self.values[i].type_name().to_string(),
+            },
+            self.spans[i],
+        )
+    }
+
+    fn list(&self, i: usize) -> Result<&Rc<RefCell<Vec<Value>>>, FunError> {
+        match &self.values[i] {
+            Value::List(values) => Ok(values),
+            _ => Err(self.mismatch(i, "List")),
+        }
+    }
+
+    fn map(&self, i: usize) -> Result<&Rc<RefCell<Map>>, FunError> {
+        match &self.values[i] {
+            Value::Map(map) => Ok(map),
+            _ => Err(self.mismatch(i, "Map")),
+        }
+    }
+
+    fn string(&self, i: usize) -> Result<&str, FunError> {
+        match &self.values[i] {
+            Value::String(string) => Ok(string),
+            _ => Err(self.mismatch(i, "String")),
+        }
+    }
+
+    /// An int or float argument, as a float.
+    fn number(&self, i: usize) -> Result<f64, FunError> {
+        self.values[i]
+            .as_float()
+            .ok_or_else(|| self.mismatch(i, "Number"))
+    }
+
+    fn int(&self, i: usize) -> Result<i64, FunError> {
+        match self.values[i] {
+            Value::Int(int) => Ok(int),
+            _ => Err(self.mismatch(i, "Int")),
+        }
+    }
+
+    fn invalid_conversion(&self, i: usize, type_: &str) -> FunError {
+        FunError::new(
+            RuntimeError::InvalidConversion {
+                value: self.values[i].repr(),
+                type_: type_.to_string(),
+            },
+            self.spans[i],
+        )
+    }
+}
+
+/// The position `index` refers to in a list of `length` elements, counting
+/// from the end if it is negative. With `end`, the position just past the
+/// last element is allowed too.
+pub fn position(index: i64, length: usize, end: bool, span: &Span) -> Result<usize, FunError> {
+    let limit = if end { length + 1 } else { length };
+    let position = if index < 0 {
+        index + length as i64
+    } else {
+        index
+    };
+    if position < 0 || position >= limit as i64 {
+        return Err(FunError::new(
+            RuntimeError::IndexOutOfBounds { index, length },
+            span,
+        ));
+    }
+    Ok(position as usize)
+}
+
+/// Checks that `value` can be used as a map key.
+pub fn check_key(value: &Value, span: &Span) -> Result<(), FunError> {
+    if Map::is_key(value) {
+        Ok(())
+    } else {
+        Err(FunError::new(
+            TypeError::InvalidKey(value.type_name().to_string()),
+            span,
+        ))
+    }
+}
+
+/// The value of `key` in `map`, which must be there.
+pub fn lookup(map: &Map, key: &Value, span: &Span) -> Result<Value, FunError> {
+    check_key(key, span)?;
+    map.get(key)
+        .cloned()
+        .ok_or_else(|| FunError::new(RuntimeError::MissingKey(key.repr()), span))
+}
+
+fn len(args: &Args) -> Result<Value, FunError> {
+    let length = match &args.values[0] {
+        Value::List(values) => values.borrow().len(),
+        Value::String(string) => string.chars().count(),
+        Value::Map(map) => map.borrow().len(),
+        _ => return Err(args.mismatch(0, "List, Map or String")),
+    };
+    Ok(Value::Int(length as i64))
+}
+
+fn push(args: &Args) -> Result<Value, FunError> {
+    args.list(0)?.borrow_mut().push(args.values[1].clone());
+    Ok(Value::None)
+}
+
+fn pop(args: &Args) -> Result<Value, FunError> {
+    args.list(0)?
+        .borrow_mut()
+        .pop()
+        .ok_or_else(|| FunError::new(RuntimeError::PopFromEmptyList, args.spans[0]))
+}
+
+fn insert(args: &Args) -> Result<Value, FunError> {
+    let list = args.list(0)?;
+    let length = list.borrow().len();
+    let index = position(args.int(1)?, length, true, args.spans[1])?;
+    list.borrow_mut().insert(index, args.values[2].clone());
+    Ok(Value::None)
+}
+
+fn remove(args: &Args) -> Result<Value, FunError> {
+    if let Value::Map(map) = &args.values[0] {
+        let key = &args.values[1];
+        let value = lookup(&map.borrow(), key, args.spans[1])?;
+        map.borrow_mut().remove(key);
+        return Ok(value);
+    }
+    let list = args.list(0)?;
+    let length = list.borrow().len();
+    let index = position(args.int(1)?, length, false, args.spans[1])?;
+    let value = list.borrow_mut().remove(index);
+    Ok(value)
+}
+
+fn get_value(args: &Args) -> Result<Value, FunError> {
+    let key = &args.values[1];
+    check_key(key, args.spans[1])?;
+    Ok(args
+        .map(0)?
+        .borrow()
+        .get(key)
+        .cloned()
+        .unwrap_or(Value::None))
+}
+
+fn keys(args: &Args) -> Result<Value, FunError> {
+    let map = args.map(0)?.borrow();
+    Ok(Value::list(
+        map.entries().iter().map(|(key, _)| key.clone()).collect(),
+    ))
+}
+
+fn values(args: &Args) -> Result<Value, FunError> {
+    let map = args.map(0)?.borrow();
+    Ok(Value::list(
+        map.entries()
+            .iter()
+            .map(|(_, value)| value.clone())
+            .collect(),
+    ))
+}
+
+/// Whether a map has a key, a list has an element or a string has a part.
+fn contains(args: &Args) -> Result<Value, FunError> {
+    let value = &args.values[1];
+    let found = match &args.values[0] {
+        Value::Map(map) => map.borrow().get(value).is_some(),
+        Value::List(values) => values.borrow().contains(value),
+        Value::String(string) => string.contains(args.string(1)?),
+        _ => return Err(args.mismatch(0, "List, Map or String")),
+    };
+    Ok(Value::Bool(found))
+}
+
+fn upper(args: &Args) -> Result<Value, FunError> {
+    Ok(Value::String(args.string(0)?.to_uppercase()))
+}
+
+fn lower(args: &Args) -> Result<Value, FunError> {
+    Ok(Value::String(args.string(0)?.to_lowercase()))
+}
+
+/// Removes whitespace from both ends.
+fn trim(args: &Args) -> Result<Value, FunError> {
+    Ok(Value::String(args.string(0)?.trim().to_string()))
+}
+
+fn chars(args: &Args) -> Result<Value, FunError> {
+    Ok(Value::list(
+        args.string(0)?
+            .chars()
+            .map(|char| Value::String(char.to_string()))
+            .collect(),
+    ))
+}
+
+fn split(args: &Args) -> Result<Value, FunError> {
+    let separator = args.string(1)?;
+    if separator.is_empty() {
+        return Err(FunError::new(RuntimeError::EmptySeparator, args.spans[1]));
+    }
+    Ok(Value::list(
+        args.string(0)?
+            .split(separator)
+            .map(|part| Value::String(part.to_string()))
+            .collect(),
+    ))
+}
+
+/// Joins the elements of a list, written the way `print` writes them.
+fn join(args: &Args) -> Result<Value, FunError> {
+    let separator = args.string(1)?;
+    let parts: Vec<String> = args
+        .list(0)?
+        .borrow()
+        .iter()
+        .map(Value::to_string)
+        .collect();
+    Ok(Value::String(parts.join(separator)))
+}
+
+/// Replaces every occurrence of `old`.
+fn replace(args: &Args) -> Result<Value, FunError> {
+    let string = args.string(0)?;
+    Ok(Value::String(
+        string.replace(args.string(1)?, args.string(2)?),
+    ))
+}
+
+fn starts_with(args: &Args) -> Result<Value, FunError> {
+    Ok(Value::Bool(args.string(0)?.starts_with(args.string(1)?)))
+}
+
+fn ends_with(args: &Args) -> Result<Value, FunError> {
+    Ok(Value::Bool(args.string(0)?.ends_with(args.string(1)?)))
+}
+
+/// The index of the first char of the first occurrence of `part`, or `none`.
+fn find(args: &Args) -> Result<Value, FunError> {
+    let string = args.string(0)?;
+    Ok(match string.find(args.string(1)?) {
+        Some(byte) => Value::Int(string[..byte].chars().count() as i64),
+        None => Value::None,
+    })
+}
+
+/// The chars from `start` up to, but not including, `end`. Negative indexes
+/// count from the end like for lists.
+fn substring(args: &Args) -> Result<Value, FunError> {
+    let chars: Vec<char> = args.string(0)?.chars().collect();
+    let start = position(args.int(1)?, chars.len(), true, args.spans[1])?;
+    let end = position(args.int(2)?, chars.len(), true, args.spans[2])?;
+    if start > end {
+        return Err(FunError::new(
+            RuntimeError::InvalidSlice { start, end },
+            args.spans[2],
+        ));
+    }
+    Ok(Value::String(chars[start..end].iter().collect()))
+}
+
+fn repeat(args: &Args) -> Result<Value, FunError> {
+    let string = args.string(0)?;
+    let times = args.int(1)?;
+    let mut repeated = String::new();
+    let length = usize::try_from(times)
+        .ok()
+        .and_then(|count| string.len().checked_mul(count));
+    // Negative counts and results too large to allocate are errors
+    match length {
+        Some(length) if repeated.try_reserve_exact(length).is_ok() => {}
+        _ => {
+            return Err(FunError::new(
+                RuntimeError::InvalidRepeat(times),
+                args.spans[1],
+            ));
+        }
+    }
+    if !string.is_empty() {
+        for _ in 0..times {
+            repeated.push_str(string);
+        }
+    }
+    Ok(Value::String(repeated))
+}
+
+/// Converts a float, dropping its fraction, or a string holding an int.
+fn to_int(args: &Args) -> Result<Value, FunError> {
+    let int = match &args.values[0] {
+        Value::Int(int) => Some(*int),
+        Value::Float(float) => value::float_to_int(float.trunc()),
+        Value::String(string) => string.trim().parse().ok(),
+        _ => return Err(args.mismatch(0, "Int, Float or String")),
+    };
+    int.map(Value::Int)
+        .ok_or_else(|| args.invalid_conversion(0, "Int"))
+}
+
+/// Converts an int, or a string holding a number.
+fn to_float(args: &Args) -> Result<Value, FunError> {
+    let float = match &args.values[0] {
+        Value::Int(int) => Some(*int as f64),
+        Value::Float(float) => Some(*float),
+        Value::String(string) => string.trim().parse().ok(),
+        _ => return Err(args.mismatch(0, "Int, Float or String")),
+    };
+    float
+        .map(Value::Float)
+        .ok_or_else(|| args.invalid_conversion(0, "Float"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::error::{ErrorKind, RuntimeError};
+    use crate::span::Span;
+    use crate::value::Value;
+
+    /// Calls the builtin named `name`, giving the kind of its error if it
+    /// fails.
+    pub(super) fn call(name: &str, values: Vec<Value>) -> Result<Value, ErrorKind> {
+        let span = Span {
+            file: Arc::from("test.fun"),
+            line: 1,
+            column: 1,
+            start: 0,
+            end: 0,
+        };
+        let builtin = super::get(name).expect("builtin exists");
+        let spans = vec![&span; values.len()];
+        builtin
+            .call(values, spans, &span)
+            .map_err(|error| error.kind().clone())
+    }
+
+    fn ints(ints: &[i64]) -> Value {
+        Value::list(ints.iter().copied().map(Value::Int).collect())
+    }
+
+    fn out_of_bounds(index: i64, length: usize) -> Result<Value, ErrorKind> {
+        Err(RuntimeError::IndexOutOfBounds { index, length }.into())
+    }
+
+    #[test]
+    fn push_and_pop() {
+        let list = ints(&[1]);
+        assert_eq!(
+            call("push", vec![list.clone(), Value::Int(2)]),
+            Ok(Value::None)
+        );
+        assert_eq!(list, ints(&[1, 2]));
+        assert_eq!(call("pop", vec![list.clone()]), Ok(Value::Int(2)));
+        assert_eq!(call("pop", vec![list.clone()]), Ok(Value::Int(1)));
+        assert_eq!(
+            call("pop", vec![list]),
+            Err(RuntimeError::PopFromEmptyList.into())
+        );
+    }
+
+    #[test]
+    fn insert_at_either_end() {
+        let list = ints(&[1, 2]);
+        call("insert", vec![list.clone(), Value::Int(2), Value::Int(3)]).unwrap();
+        call("insert", vec![list.clone(), Value::Int(0), Value::Int(0)]).unwrap();
+        call("insert", vec![list.clone(), Value::Int(-1), Value::Int(4)]).unwrap();
+        assert_eq!(list, ints(&[0, 1, 2, 4, 3]));
+        assert_eq!(
+            call("insert", vec![list.clone(), Value::Int(6), Value::Int(5)]),
+            out_of_bounds(6, 5)
+        );
+        assert_eq!(
+            call("insert", vec![list, Value::Int(-6), Value::Int(5)]),
+            out_of_bounds(-6, 5)
+        );
+    }
+
+    #[test]
+    fn remove_from_list() {
+        let list = ints(&[1, 2, 3]);
+        assert_eq!(
+            call("remove", vec![list.clone(), Value::Int(-1)]),
+            Ok(Value::Int(3))
+        );
+        assert_eq!(
+            call("remove", vec![list.clone(), Value::Int(0)]),
+            Ok(Value::Int(1))
+        );
+        assert_eq!(list, ints(&[2]));
+        assert_eq!(
+            call("remove", vec![list, Value::Int(1)]),
+            out_of_bounds(1, 1)
+        );
+    }
+
+    #[test]
+    fn conversions() {
+        let cases = [
+            ("to_int", Value::Float(-2.7), Value::Int(-2)),
+            ("to_int", Value::String(" 42 ".to_string()), Value::Int(42)),
+            ("to_float", Value::Int(3), Value::Float(3.0)),
+            (
+                "to_float",
+                Value::String("2.5".to_string()),
+                Value::Float(2.5),
+            ),
+        ];
+        for (name, value, expected) in cases {
+            assert_eq!(call(name, vec![value]), Ok(expected));
+        }
+
+        let invalid = |value: &str, type_: &str| {
+            Err(RuntimeError::InvalidConversion {
+                value: value.to_string(),
+                type_: type_.to_string(),
+            }
+            .into())
+        };
+        assert_eq!(
+            call("to_int", vec![Value::String("2.5".to_string())]),
+            invalid(r#""2.5""#, "Int")
+        );
+        assert_eq!(
+            call("to_int", vec![Value::Float(1e19)]),
+            invalid("1e19", "Int")
+        );
+        assert_eq!(
+            call("to_int", vec![Value::Float(f64::NAN)]),
+            invalid("NaN", "Int")
+        );
+        assert_eq!(
+            call("to_float", vec![Value::String("x".to_string())]),
+            invalid(r#""x""#, "Float")
+        );
+    }
+
+    fn string(string: &str) -> Value {
+        Value::String(string.to_string())
+    }
+
+    fn strings(strings: &[&str]) -> Value {
+        Value::list(strings.iter().map(|part| string(part)).collect())
+    }
+
+    #[test]
+    fn strings_count_chars() {
+        assert_eq!(call("len", vec![string("héllo")]), Ok(Value::Int(5)));
+        assert_eq!(call("chars", vec![string("hé")]), Ok(strings(&["h", "é"])));
+        assert_eq!(
+            call("find", vec![string("héllo"), string("llo")]),
+            Ok(Value::Int(2))
+        );
+        assert_eq!(
+            call("find", vec![string("abc"), string("x")]),
+            Ok(Value::None)
+        );
+        let substring = |start, end| {
+            call(
+                "substring",
+                vec![string("héllo"), Value::Int(start), Value::Int(end)],
+            )
+        };
+        assert_eq!(substring(1, 3), Ok(string("él")));
+        assert_eq!(substring(-3, 5), Ok(string("llo")));
+        assert_eq!(substring(5, 5), Ok(string("")));
+        assert_eq!(substring(0, 6), out_of_bounds(6, 5));
+        assert_eq!(
+            substring(3, 1),
+            Err(RuntimeError::InvalidSlice { start: 3, end: 1 }.into())
+        );
+    }
+
+    #[test]
+    fn string_builtins() {
+        let cases = [
+            ("upper", vec![string("Straße")], string("STRASSE")),
+            ("lower", vec![string("ÀB")], string("àb")),
+            ("trim", vec![string(" \t a b \n")], string("a b")),
+            (
+                "replace",
+                vec![string("a-b-c"), string("-"), string("+")],
+                string("a+b+c"),
+            ),
+            (
+                "contains",
+                vec![string("abc"), string("bc")],
+                Value::Bool(true),
+            ),
+            (
+                "starts_with",
+                vec![string("abc"), string("ab")],
+                Value::Bool(true),
+            ),
+            (
+                "ends_with",
+                vec![string("abc"), string("ab")],
+                Value::Bool(false),
+            ),
+            (
+                "repeat",
+                vec![string("ab"), Value::Int(3)],
+                string("ababab"),
+            ),
+            ("repeat", vec![string("ab"), Value::Int(0)], string("")),
+        ];
+        for (name, values, expected) in cases {
+            assert_eq!(call(name, values), Ok(expected), "{}", name);
+        }
+    }
+
+    #[test]
+    fn split_and_join() {
+        assert_eq!(
+            call("split", vec![string("a,b,,c"), string(",")]),
+            Ok(strings(&["a", "b", "", "c"]))
+        );
+        assert_eq!(
+            call("split", vec![string("abc"), string("")]),
+            Err(RuntimeError::EmptySeparator.into())
+        );
+        let parts = Value::list(vec![string("a"), Value::Int(1), Value::Float(2.0)]);
+        assert_eq!(
+            call("join", vec![parts, string(", ")]),
+            Ok(string("a, 1, 2.0"))
+        );
+    }
+
+    #[test]
+    fn invalid_repeats() {
+        for times in [-1, i64::MAX] {
+            assert_eq!(
+                call("repeat", vec![string("ab"), Value::Int(times)]),
+                Err(RuntimeError::InvalidRepeat(times).into())
+            );
+        }
+    }
+}
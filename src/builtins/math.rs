@@ -0,0 +1,428 @@
+use std::f64::consts;
+
+use super::{Args, Builtin, Module};
+use crate::error::{FunError, RuntimeError};
+use crate::value::{self, Value};
+
+pub const MODULE: Module = Module {
+    name: "math",
+    constants: &[("pi", consts::PI), ("e", consts::E)],
+    functions: &[
+        Builtin {
+            name: "math.abs",
+            params: &["x"],
+            function: abs,
+        },
+        Builtin {
+            name: "math.min",
+            params: &["a", "b"],
+            function: min,
+        },
+        Builtin {
+            name: "math.max",
+            params: &["a", "b"],
+            function: max,
+        },
+        Builtin {
+            name: "math.clamp",
+            params: &["x", "low", "high"],
+            function: clamp,
+        },
+        Builtin {
+            name: "math.floor",
+            params: &["x"],
+            function: floor,
+        },
+        Builtin {
+            name: "math.ceil",
+            params: &["x"],
+            function: ceil,
+        },
+        Builtin {
+            name: "math.round",
+            params: &["x"],
+            function: round,
+        },
+        Builtin {
+            name: "math.sqrt",
+            params: &["x"],
+            function: sqrt,
+        },
+        Builtin {
+            name: "math.pow",
+            params: &["base", "exponent"],
+            function: pow,
+        },
+        Builtin {
+            name: "math.exp",
+            params: &["x"],
+            function: exp,
+        },
+        Builtin {
+            name: "math.log",
+            params: &["x"],
+            function: log,
+        },
+        Builtin {
+            name: "math.sin",
+            params: &["x"],
+            function: sin,
+        },
+        Builtin {
+            name: "math.cos",
+            params: &["x"],
+            function: cos,
+        },
+        Builtin {
+            name: "math.tan",
+            params: &["x"],
+            function: tan,
+        },
+        Builtin {
+            name: "math.asin",
+            params: &["x"],
+            function: asin,
+        },
+        Builtin {
+            name: "math.acos",
+            params: &["x"],
+            function: acos,
+        },
+        Builtin {
+            name: "math.atan",
+            params: &["x"],
+            function: atan,
+        },
+        Builtin {
+            name: "math.atan2",
+            params: &["y", "x"],
+            function: atan2,
+        },
+    ],
+};
+
+/// Checks the result of a float function of the arguments: one that is not a
+/// number means the arguments are outside the domain of the function, and an
+/// infinite one is too large. Both are fine if an argument already was.
+fn float_result(args: &Args, result: f64) -> Result<Value, FunError> {
+    let finite = args
+        .values
+        .iter()
+        .all(|value| value.as_float().is_some_and(f64::is_finite));
+    let error = if !finite || result.is_finite() {
+        return Ok(Value::Float(result));
+    } else if result.is_nan() {
+        let values: Vec<String> = args.values.iter().map(Value::repr).collect();
+        RuntimeError::Domain {
+            function: args.builtin.name.to_string(),
+            args: values.join(" and "),
+        }
+    } else {
+        RuntimeError::OutOfRange(args.builtin.name.to_string())
+    };
+    Err(FunError::new(error, args.spans[0]))
+}
+
+fn unary(args: &Args, function: fn(f64) -> f64) -> Result<Value, FunError> {
+    float_result(args, function(args.number(0)?))
+}
+
+/// Applies a float function that gives a whole number, keeping ints as they
+/// are.
+fn rounding(args: &Args, function: fn(f64) -> f64) -> Result<Value, FunError> {
+    if let Value::Int(int) = args.values[0] {
+        return Ok(Value::Int(int));
+    }
+    let float = function(args.number(0)?);
+    value::float_to_int(float).map(Value::Int).ok_or_else(|| {
+        FunError::new(
+            RuntimeError::InvalidConversion {
+                value: Value::Float(float).repr(),
+                type_: "Int".to_string(),
+            },
+            args.spans[0],
+        )
+    })
+}
+
+fn abs(args: &Args) -> Result<Value, FunError> {
+    match args.values[0] {
+        Value::Int(int) => int.checked_abs().map(Value::Int).ok_or_else(|| {
+            FunError::new(
+                RuntimeError::IntegerOverflow(args.builtin.name.to_string()),
+                args.spans[0],
+            )
+        }),
+        _ => unary(args, f64::abs),
+    }
+}
+
+fn min(args: &Args) -> Result<Value, FunError> {
+    match (&args.values[0], &args.values[1]) {
+        (Value::Int(a), Value::Int(b)) => Ok(Value::Int(*a.min(b))),
+        _ => Ok(Value::Float(args.number(0)?.min(args.number(1)?))),
+    }
+}
+
+fn max(args: &Args) -> Result<Value, FunError> {
+    match (&args.values[0], &args.values[1]) {
+        (Value::Int(a), Value::Int(b)) => Ok(Value::Int(*a.max(b))),
+        _ => Ok(Value::Float(args.number(0)?.max(args.number(1)?))),
+    }
+}
+
+/// `x` moved into the range from `low` to `high`.
+fn clamp(args: &Args) -> Result<Value, FunError> {
+    let (x, low, high) = (args.number(0)?, args.number(1)?, args.number(2)?);
+    // Ints are compared as they are, since large ones can round to the same
+    // float. Floats that are not numbers are not ordered.
+    let ordered = match (&args.values[1], &args.values[2]) {
+        (Value::Int(low), Value::Int(high)) => low <= high,
+        _ => low <= high,
+    };
+    if !ordered {
+        return Err(FunError::new(
+            RuntimeError::InvalidBounds {
+                low: args.values[1].repr(),
+                high: args.values[2].repr(),
+            },
+            args.spans[2],
+        ));
+    }
+    match (&args.values[0], &args.values[1], &args.values[2]) {
+        (Value::Int(x), Value::Int(low), Value::Int(high)) => Ok(Value::Int(*x.clamp(low, high))),
+        _ => Ok(Value::Float(x.clamp(low, high))),
+    }
+}
+
+fn floor(args: &Args) -> Result<Value, FunError> {
+    rounding(args, f64::floor)
+}
+
+fn ceil(args: &Args) -> Result<Value, FunError> {
+    rounding(args, f64::ceil)
+}
+
+/// Rounds half-way cases away from zero.
+fn round(args: &Args) -> Result<Value, FunError> {
+    rounding(args, f64::round)
+}
+
+fn sqrt(args: &Args) -> Result<Value, FunError> {
+    unary(args, f64::sqrt)
+}
+
+/// An int for an int base and a positive int exponent, a float otherwise.
+fn pow(args: &Args) -> Result<Value, FunError> {
+    if let (Value::Int(base), Value::Int(exponent)) = (&args.values[0], &args.values[1]) {
+        if *exponent >= 0 {
+            let result = match u32::try_from(*exponent) {
+                Ok(exponent) => base.checked_pow(exponent),
+                // Only these stay in range with such a large exponent
+                Err(_) => match base {
+                    0 | 1 => Some(*base),
+                    -1 => Some(if exponent % 2 == 0 { 1 } else { -1 }),
+                    _ => None,
+                },
+            };
+            return result.map(Value::Int).ok_or_else(|| {
+                FunError::new(
+                    RuntimeError::IntegerOverflow(args.builtin.name.to_string()),
+                    args.spans[1],
+                )
+            });
+        }
+    }
+    float_result(args, args.number(0)?.powf(args.number(1)?))
+}
+
+fn exp(args: &Args) -> Result<Value, FunError> {
+    unary(args, f64::exp)
+}
+
+/// The natural logarithm.
+fn log(args: &Args) -> Result<Value, FunError> {
+    // The logarithm of 0 is infinite, but it is an error like that of a
+    // negative number rather than a result that is too large
+    unary(args, |x| if x == 0.0 { f64::NAN } else { x.ln() })
+}
+
+fn sin(args: &Args) -> Result<Value, FunError> {
+    unary(args, f64::sin)
+}
+
+fn cos(args: &Args) -> Result<Value, FunError> {
+    unary(args, f64::cos)
+}
+
+fn tan(args: &Args) -> Result<Value, FunError> {
+    unary(args, f64::tan)
+}
+
+fn asin(args: &Args) -> Result<Value, FunError> {
+    unary(args, f64::asin)
+}
+
+fn acos(args: &Args) -> Result<Value, FunError> {
+    unary(args, f64::acos)
+}
+
+fn atan(args: &Args) -> Result<Value, FunError> {
+    unary(args, f64::atan)
+}
+
+/// The angle of the point `(x, y)`.
+fn atan2(args: &Args) -> Result<Value, FunError> {
+    float_result(args, args.number(0)?.atan2(args.number(1)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts;
+
+    use crate::builtins::tests::call;
+    use crate::error::{ErrorKind, RuntimeError};
+    use crate::value::Value;
+
+    fn domain(function: &str, args: &str) -> Result<Value, ErrorKind> {
+        Err(RuntimeError::Domain {
+            function: function.to_string(),
+            args: args.to_string(),
+        }
+        .into())
+    }
+
+    fn overflow(function: &str) -> Result<Value, ErrorKind> {
+        Err(RuntimeError::IntegerOverflow(function.to_string()).into())
+    }
+
+    #[test]
+    fn constants() {
+        assert_eq!(super::MODULE.member("pi"), Some(Value::Float(consts::PI)));
+        assert_eq!(super::MODULE.member("e"), Some(Value::Float(consts::E)));
+    }
+
+    #[test]
+    fn domain_errors() {
+        let cases = [
+            ("math.sqrt", vec![Value::Int(-1)], "-1"),
+            ("math.log", vec![Value::Int(0)], "0"),
+            ("math.log", vec![Value::Float(-1.5)], "-1.5"),
+            ("math.asin", vec![Value::Int(2)], "2"),
+            ("math.acos", vec![Value::Float(-1.1)], "-1.1"),
+            (
+                "math.pow",
+                vec![Value::Int(-8), Value::Float(0.5)],
+                "-8 and 0.5",
+            ),
+        ];
+        for (name, values, args) in cases {
+            assert_eq!(call(name, values), domain(name, args));
+        }
+        assert_eq!(
+            call("math.sqrt", vec![Value::Int(4)]),
+            Ok(Value::Float(2.0))
+        );
+    }
+
+    #[test]
+    fn range_errors() {
+        let cases = [
+            ("math.exp", vec![Value::Int(1000)]),
+            ("math.pow", vec![Value::Float(10.0), Value::Int(400)]),
+        ];
+        for (name, values) in cases {
+            assert_eq!(
+                call(name, values),
+                Err(RuntimeError::OutOfRange(name.to_string()).into())
+            );
+        }
+        // Arguments that are already infinite or not numbers are passed on
+        let infinity = Value::Float(f64::INFINITY);
+        assert_eq!(call("math.sqrt", vec![infinity.clone()]), Ok(infinity));
+        let result = call("math.sin", vec![Value::Float(f64::NAN)]);
+        assert!(matches!(result, Ok(Value::Float(float)) if float.is_nan()));
+    }
+
+    #[test]
+    fn int_powers() {
+        let pow = |base, exponent| call("math.pow", vec![Value::Int(base), Value::Int(exponent)]);
+        assert_eq!(pow(2, 10), Ok(Value::Int(1024)));
+        assert_eq!(pow(2, -1), Ok(Value::Float(0.5)));
+        assert_eq!(pow(1, i64::MAX), Ok(Value::Int(1)));
+        assert_eq!(pow(-1, i64::MAX), Ok(Value::Int(-1)));
+        assert_eq!(pow(2, 63), overflow("math.pow"));
+        assert_eq!(pow(2, i64::MAX), overflow("math.pow"));
+    }
+
+    #[test]
+    fn rounding() {
+        let cases = [
+            ("math.round", Value::Float(2.5), Value::Int(3)),
+            ("math.round", Value::Float(-2.5), Value::Int(-3)),
+            ("math.floor", Value::Float(-0.5), Value::Int(-1)),
+            ("math.ceil", Value::Float(0.2), Value::Int(1)),
+            ("math.floor", Value::Int(i64::MAX), Value::Int(i64::MAX)),
+        ];
+        for (name, value, expected) in cases {
+            assert_eq!(call(name, vec![value]), Ok(expected), "{}", name);
+        }
+        assert_eq!(
+            call("math.floor", vec![Value::Float(1e300)]),
+            Err(RuntimeError::InvalidConversion {
+                value: "1e300".to_string(),
+                type_: "Int".to_string(),
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn abs_min_max() {
+        assert_eq!(call("math.abs", vec![Value::Int(-3)]), Ok(Value::Int(3)));
+        assert_eq!(
+            call("math.abs", vec![Value::Float(-2.5)]),
+            Ok(Value::Float(2.5))
+        );
+        assert_eq!(
+            call("math.abs", vec![Value::Int(i64::MIN)]),
+            overflow("math.abs")
+        );
+        assert_eq!(
+            call("math.min", vec![Value::Int(1), Value::Int(2)]),
+            Ok(Value::Int(1))
+        );
+        assert_eq!(
+            call("math.max", vec![Value::Int(1), Value::Float(2.5)]),
+            Ok(Value::Float(2.5))
+        );
+    }
+
+    #[test]
+    fn clamp() {
+        let clamp = |x, low, high| call("math.clamp", vec![x, low, high]);
+        assert_eq!(
+            clamp(Value::Int(5), Value::Int(0), Value::Int(3)),
+            Ok(Value::Int(3))
+        );
+        assert_eq!(
+            clamp(Value::Float(-1.5), Value::Int(0), Value::Int(3)),
+            Ok(Value::Float(0.0))
+        );
+        let invalid = |low: &str, high: &str| {
+            Err(RuntimeError::InvalidBounds {
+                low: low.to_string(),
+                high: high.to_string(),
+            }
+            .into())
+        };
+        assert_eq!(
+            clamp(Value::Int(1), Value::Int(3), Value::Int(0)),
+            invalid("3", "0")
+        );
+        assert_eq!(
+            clamp(Value::Int(1), Value::Int(0), Value::Float(f64::NAN)),
+            invalid("0", "NaN")
+        );
+    }
+}
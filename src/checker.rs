@@ -0,0 +1,2110 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+
+use super::ast::*;
+use super::builtins::{self, Builtin, BUILTINS, MODULES};
+use super::error::{ErrorKind, FunError, SyntaxError, TypeError};
+use super::span::Span;
+use super::value::Value;
+
+/// The type of a value as far as the checker can tell.
+#[derive(Debug, Clone, PartialEq)]
+enum Ty {
+    /// Only known at runtime, such as the type of an unannotated parameter.
+    Unknown,
+    String,
+    Int,
+    Float,
+    /// An int or a float.
+    Number,
+    Bool,
+    /// The type of `none` itself.
+    None,
+    /// `None` for a function whose signature is not known.
+    Function(Option<Rc<Signature>>),
+    /// A builtin function, checked by its name since the types of its
+    /// parameters can depend on each other.
+    Builtin(&'static str),
+    /// A module, by its name.
+    Module(&'static str),
+    Optional(Box<Ty>),
+    List(Box<Ty>),
+    Map(Box<Ty>, Box<Ty>),
+    /// An instance of the struct, or a variant of the enum, with this name.
+    Named(String),
+    /// The name of a struct itself, used to build instances.
+    StructType(Rc<Struct>),
+    /// The name of an enum itself.
+    EnumType(Rc<Enum>),
+}
+
+#[derive(Debug, PartialEq)]
+struct Signature {
+    /// `None` for anonymous functions and function types.
+    name: Option<String>,
+    /// Parameter names are `None` for function types.
+    params: Vec<(Option<String>, Ty)>,
+    return_type: Ty,
+}
+
+impl Ty {
+    fn of_function(function: &Function) -> Ty {
+        Ty::Function(Some(Rc::new(Signature {
+            name: function.name.clone(),
+            params: function
+                .params
+                .iter()
+                .map(|param| (Some(param.name.clone()), Ty::annotated(&param.type_)))
+                .collect(),
+            return_type: Ty::annotated(&function.return_type),
+        })))
+    }
+
+    /// The type of the name a variant is declared as: a value of the enum,
+    /// or a function building one from the values the variant carries.
+    fn of_variant(enum_: &Enum, variant: &Variant) -> Ty {
+        let type_ = Ty::Named(enum_.name.clone());
+        if variant.types.is_empty() {
+            return type_;
+        }
+        Ty::Function(Some(Rc::new(Signature {
+            name: Some(variant.name.clone()),
+            params: variant
+                .types
+                .iter()
+                .map(|type_| (None, Ty::from(type_)))
+                .collect(),
+            return_type: type_,
+        })))
+    }
+
+    fn annotated(type_: &Option<Type>) -> Ty {
+        type_.as_ref().map_or(Ty::Unknown, Ty::from)
+    }
+
+    /// Whether a value of type `found` passes the interpreter's check for a
+    /// value of type `self`, which for functions only compares arity.
+    fn accepts(&self, found: &Ty) -> bool {
+        match (self, found) {
+            (Ty::Unknown, _) | (_, Ty::Unknown) => true,
+            (Ty::Optional(_), Ty::None) => true,
+            (Ty::Optional(expected), Ty::Optional(found)) => expected.accepts(found),
+            (Ty::Optional(expected), found) => expected.accepts(found),
+            (Ty::Function(Some(expected)), Ty::Function(Some(found))) => {
+                expected.params.len() == found.params.len()
+            }
+            (Ty::Function(_), Ty::Function(_)) => true,
+            (Ty::Function(Some(expected)), Ty::Builtin(name)) => {
+                expected.params.len() == builtin(name).params.len()
+            }
+            (Ty::Function(_), Ty::Builtin(_)) => true,
+            (Ty::List(expected), Ty::List(found)) => expected.accepts(found),
+            (Ty::Map(expected_key, expected), Ty::Map(found_key, found)) => {
+                expected_key.accepts(found_key) && expected.accepts(found)
+            }
+            // A number may turn out to be either
+            (Ty::Number, Ty::Int | Ty::Float) | (Ty::Int | Ty::Float, Ty::Number) => true,
+            (expected, found) => expected == found,
+        }
+    }
+
+    /// Whether the interpreter allows `==` between values of the two types:
+    /// anything can be compared with `none`, other values need the same type
+    /// name.
+    fn comparable(&self, other: &Ty) -> bool {
+        match (self.inner(), other.inner()) {
+            (Ty::Unknown | Ty::None, _) | (_, Ty::Unknown | Ty::None) => true,
+            (Ty::Function(_) | Ty::Builtin(_), Ty::Function(_) | Ty::Builtin(_)) => true,
+            (Ty::List(_), Ty::List(_)) | (Ty::Map(..), Ty::Map(..)) => true,
+            (left, right) if left.is_number() && right.is_number() => true,
+            (left, right) => left == right,
+        }
+    }
+
+    /// The type without `none`, for optional types.
+    fn inner(&self) -> &Ty {
+        match self {
+            Ty::Optional(inner) => inner,
+            type_ => type_,
+        }
+    }
+
+    /// The type a variable declared without an annotation keeps, which for
+    /// functions is any function.
+    fn widened(&self) -> Ty {
+        match self {
+            Ty::Function(_) | Ty::Builtin(_) => Ty::Function(None),
+            Ty::List(element) => Ty::List(Box::new(element.widened())),
+            Ty::Map(key, value) => Ty::Map(Box::new(key.widened()), Box::new(value.widened())),
+            type_ => type_.clone(),
+        }
+    }
+
+    /// Whether the type says more than `Unknown`, down to the elements of a
+    /// list, such as for the empty list.
+    fn is_known(&self) -> bool {
+        match self {
+            Ty::Unknown => false,
+            Ty::Optional(type_) | Ty::List(type_) => type_.is_known(),
+            Ty::Map(key, value) => key.is_known() && value.is_known(),
+            _ => true,
+        }
+    }
+
+    fn is_number(&self) -> bool {
+        matches!(self, Ty::Int | Ty::Float | Ty::Number)
+    }
+
+    /// Whether values of this type may be map keys.
+    fn is_key(&self) -> bool {
+        self.is_number() || matches!(self, Ty::Unknown | Ty::String | Ty::Bool)
+    }
+
+    /// The type of arithmetic on numbers of the two types, which gives an int
+    /// only for two ints.
+    fn arithmetic(&self, other: &Ty) -> Ty {
+        match (self, other) {
+            (Ty::Int, Ty::Int) => Ty::Int,
+            (Ty::Float, _) | (_, Ty::Float) => Ty::Float,
+            _ => Ty::Number,
+        }
+    }
+
+    /// The type of a value that is either of type `self` or of `other`.
+    fn join(&self, other: &Ty) -> Ty {
+        match (self, other) {
+            _ if self == other => self.clone(),
+            (Ty::None, type_) | (type_, Ty::None) => Ty::Optional(Box::new(type_.inner().clone())),
+            (Ty::Optional(inner), type_) | (type_, Ty::Optional(inner)) if **inner == *type_ => {
+                Ty::Optional(inner.clone())
+            }
+            (Ty::List(left), Ty::List(right)) => Ty::List(Box::new(left.join(right))),
+            (Ty::Map(left_key, left), Ty::Map(right_key, right)) => Ty::Map(
+                Box::new(left_key.join(right_key)),
+                Box::new(left.join(right)),
+            ),
+            (Ty::Function(_) | Ty::Builtin(_), Ty::Function(_) | Ty::Builtin(_)) => {
+                Ty::Function(None)
+            }
+            (left, right) if left.is_number() && right.is_number() => Ty::Number,
+            _ => Ty::Unknown,
+        }
+    }
+}
+
+impl From<&Type> for Ty {
+    fn from(type_: &Type) -> Self {
+        match type_ {
+            Type::String => Ty::String,
+            Type::Int => Ty::Int,
+            Type::Float => Ty::Float,
+            Type::Number => Ty::Number,
+            Type::Bool => Ty::Bool,
+            Type::Function {
+                params,
+                return_type,
+            } => Ty::Function(Some(Rc::new(Signature {
+                name: None,
+                params: params.iter().map(|param| (None, Ty::from(param))).collect(),
+                return_type: return_type.as_deref().map_or(Ty::Unknown, Ty::from),
+            }))),
+            Type::Optional(type_) => Ty::Optional(Box::new(Ty::from(&**type_))),
+            Type::List(type_) => Ty::List(Box::new(Ty::from(&**type_))),
+            Type::Map(key, value) => {
+                Ty::Map(Box::new(Ty::from(&**key)), Box::new(Ty::from(&**value)))
+            }
+            Type::Named(name) => Ty::Named(name.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ty::Unknown => write!(f, "Unknown"),
+            Ty::String => write!(f, "String"),
+            Ty::Int => write!(f, "Int"),
+            Ty::Float => write!(f, "Float"),
+            Ty::Number => write!(f, "Number"),
+            Ty::Bool => write!(f, "Bool"),
+            Ty::None => write!(f, "None"),
+            Ty::Function(None) | Ty::Builtin(_) => write!(f, "Function"),
+            Ty::Module(_) => write!(f, "Module"),
+            Ty::Function(Some(signature)) => {
+                write!(f, "fn(")?;
+                for (i, (_, param)) in signature.params.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", param)?;
+                }
+                write!(f, ")")?;
+                if signature.return_type != Ty::Unknown {
+                    write!(f, ": {}", signature.return_type)?;
+                }
+                Ok(())
+            }
+            Ty::Optional(type_) => write!(f, "{}?", type_),
+            Ty::List(type_) => write!(f, "List<{}>", type_),
+            Ty::Map(key, value) => write!(f, "Map<{}, {}>", key, value),
+            Ty::Named(name) => write!(f, "{}", name),
+            Ty::StructType(_) => write!(f, "Struct"),
+            Ty::EnumType(_) => write!(f, "Enum"),
+        }
+    }
+}
+
+/// What the checker knows about a variable.
+#[derive(Debug, Clone)]
+struct Binding {
+    /// The type every value assigned to it must have.
+    declared: Ty,
+    /// The type of its value at this point, which can be narrower than
+    /// `declared`, such as after comparing it with `none`.
+    current: Ty,
+}
+
+impl Binding {
+    fn new(type_: Ty) -> Self {
+        Self {
+            declared: type_.clone(),
+            current: type_,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct FunctionContext {
+    name: String,
+    return_type: Option<Type>,
+    /// The index in `scopes` of the scope holding the parameters.
+    scope: usize,
+}
+
+/// Finds type errors in a resolved program without running it.
+///
+/// Checking is gradual: values whose type depends on how the program runs,
+/// such as unannotated parameters, are `Unknown` and accepted anywhere. The
+/// element type of a list is inferred from its literal, so storing another
+/// type in it is reported even though the interpreter would allow it.
+#[derive(Clone)]
+pub struct Checker {
+    globals: HashMap<String, Binding>,
+    /// One map per open local scope, innermost last.
+    scopes: Vec<HashMap<String, Binding>>,
+    /// Globals declared more than once, whose type inside a function depends
+    /// on when it is called.
+    redeclared: HashSet<String>,
+    /// Variables that a function assigns although they are declared outside
+    /// it. Calling the function can set them to `none` at any point, so
+    /// comparing them with `none` does not narrow them. Globals are kept
+    /// apart from locals, which are only told apart by name.
+    assigned_globals: HashSet<String>,
+    assigned_locals: HashSet<String>,
+    /// The functions being checked, innermost last.
+    functions: Vec<FunctionContext>,
+    /// Every struct and enum declared so far, and those declared at the top
+    /// level of the program being checked, so types can name them before
+    /// they are declared.
+    structs: HashMap<String, Rc<Struct>>,
+    enums: HashMap<String, Rc<Enum>>,
+    errors: Vec<FunError>,
+}
+
+impl Default for Checker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        Self {
+            globals: BUILTINS
+                .iter()
+                .map(|builtin| (builtin.name, Ty::Builtin(builtin.name)))
+                .chain(
+                    MODULES
+                        .iter()
+                        .map(|module| (module.name, Ty::Module(module.name))),
+                )
+                .map(|(name, type_)| (name.to_string(), Binding::new(type_)))
+                .collect(),
+            scopes: Vec::new(),
+            redeclared: HashSet::new(),
+            assigned_globals: HashSet::new(),
+            assigned_locals: HashSet::new(),
+            functions: Vec::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Checks `statements`, returning every type error found. Globals declared
+    /// by earlier calls stay known.
+    pub fn check(&mut self, statements: &[Stmt]) -> Result<(), Vec<FunError>> {
+        let mut declared = HashSet::new();
+        for statement in statements {
+            let names = match &statement.kind {
+                StmtKind::Let { name, .. } => vec![name],
+                StmtKind::Struct(struct_) => {
+                    self.structs
+                        .insert(struct_.name.clone(), Rc::clone(struct_));
+                    vec![&struct_.name]
+                }
+                StmtKind::Enum(enum_) => {
+                    self.enums.insert(enum_.name.clone(), Rc::clone(enum_));
+                    let variants = enum_.variants.iter().map(|variant| &variant.name);
+                    [&enum_.name].into_iter().chain(variants).collect()
+                }
+                StmtKind::Function(function) => match &function.name {
+                    Some(name) => vec![name],
+                    None => continue,
+                },
+                _ => continue,
+            };
+            for name in names {
+                if !declared.insert(name) || self.globals.contains_key(name) {
+                    self.redeclared.insert(name.clone());
+                }
+            }
+        }
+
+        assignments(statements, None, &mut |name, global| {
+            let assigned = if global {
+                &mut self.assigned_globals
+            } else {
+                &mut self.assigned_locals
+            };
+            assigned.insert(name.to_string());
+        });
+
+        self.statements(statements);
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn error(&mut self, error: impl Into<ErrorKind>, span: &Span) {
+        self.errors.push(FunError::new(error, span));
+    }
+
+    fn mismatch(&mut self, expected: impl ToString, found: &Ty, span: &Span) {
+        self.error(
+            TypeError::Mismatch {
+                expected: expected.to_string(),
+                found: found.to_string(),
+            },
+            span,
+        );
+    }
+
+    /// Reports using a value of an optional type as if it could not be
+    /// `none`, and returns the type without `none`.
+    fn checked(&mut self, type_: Ty, span: &Span) -> Ty {
+        match type_ {
+            Ty::Optional(inner) => {
+                self.error(
+                    TypeError::UncheckedOptional(Ty::Optional(inner.clone()).to_string()),
+                    span,
+                );
+                *inner
+            }
+            type_ => type_,
+        }
+    }
+
+    /// Reports struct and enum names in `type_` that are not declared, and
+    /// returns whether there were none.
+    fn known_type(&mut self, type_: &Type, span: &Span) -> bool {
+        match type_ {
+            Type::String | Type::Int | Type::Float | Type::Number | Type::Bool => true,
+            Type::Function {
+                params,
+                return_type,
+            } => {
+                let mut known = true;
+                for param in params.iter().chain(return_type.as_deref()) {
+                    known &= self.known_type(param, span);
+                }
+                known
+            }
+            Type::Optional(type_) | Type::List(type_) => self.known_type(type_, span),
+            Type::Map(key, value) => {
+                let known = self.known_type(key, span);
+                self.known_type(value, span) && known
+            }
+            Type::Named(name) => {
+                let known = self.structs.contains_key(name) || self.enums.contains_key(name);
+                if !known {
+                    self.error(SyntaxError::UnknownType(name.clone()), span);
+                }
+                known
+            }
+        }
+    }
+
+    fn scoped<T>(&mut self, names: Vec<(String, Ty)>, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(
+            names
+                .into_iter()
+                .map(|(name, type_)| (name, Binding::new(type_)))
+                .collect(),
+        );
+        let result = f(self);
+        self.scopes.pop();
+        result
+    }
+
+    fn declare(&mut self, name: &str, binding: Binding) {
+        match self.scopes.last_mut() {
+            Some(scope) => scope.insert(name.to_string(), binding),
+            None => self.globals.insert(name.to_string(), binding),
+        };
+    }
+
+    /// Where a variable is declared: `Some(index)` of a local scope or `None`
+    /// for a global. Returns `None` if its type cannot be known here.
+    fn find(&self, name: &str) -> Option<Option<usize>> {
+        if let Some(index) = self
+            .scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(name))
+        {
+            return Some(Some(index));
+        }
+        let in_function = !self.functions.is_empty();
+        if !self.globals.contains_key(name) || (in_function && self.redeclared.contains(name)) {
+            return None;
+        }
+        Some(None)
+    }
+
+    fn binding(&mut self, location: Option<usize>, name: &str) -> &mut Binding {
+        let scope = match location {
+            Some(index) => &mut self.scopes[index],
+            None => &mut self.globals,
+        };
+        scope.get_mut(name).expect("found by `find`")
+    }
+
+    /// Whether the variable at `location` belongs to the function being
+    /// checked, so what is known about its current value can be trusted.
+    /// Variables of enclosing scopes may change before the function is called.
+    fn owned(&self, location: Option<usize>) -> bool {
+        match (self.functions.last(), location) {
+            (None, _) => true,
+            (Some(function), Some(index)) => index >= function.scope,
+            (Some(_), None) => false,
+        }
+    }
+
+    fn type_of(&mut self, name: &str) -> Ty {
+        let Some(location) = self.find(name) else {
+            return Ty::Unknown;
+        };
+        let owned = self.owned(location);
+        let binding = self.binding(location, name);
+        if owned {
+            binding.current.clone()
+        } else {
+            binding.declared.clone()
+        }
+    }
+
+    /// The binding of `name` with `none` ruled out, if that makes it narrower.
+    fn narrow(&mut self, name: &str) -> Option<Binding> {
+        let location = self.find(name)?;
+        let assigned = match location {
+            Some(_) => &self.assigned_locals,
+            None => &self.assigned_globals,
+        };
+        if assigned.contains(name) {
+            return None;
+        }
+        let owned = self.owned(location);
+        let binding = self.binding(location, name);
+        let current = if owned {
+            &binding.current
+        } else {
+            &binding.declared
+        };
+        match current {
+            Ty::Optional(inner) => Some(Binding {
+                declared: binding.declared.clone(),
+                current: (**inner).clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Checks `f` in a scope where the variables in `names` are not `none`.
+    fn narrowed<T>(&mut self, names: Vec<String>, f: impl FnOnce(&mut Self) -> T) -> T {
+        let bindings = names
+            .into_iter()
+            .filter_map(|name| {
+                let binding = self.narrow(&name)?;
+                Some((name, binding))
+            })
+            .collect();
+        self.scopes.push(bindings);
+        let result = f(self);
+        self.scopes.pop();
+        result
+    }
+
+    /// Rules out `none` for the variables in `names` in the rest of the
+    /// current scope.
+    fn narrow_here(&mut self, names: Vec<String>) {
+        for name in names {
+            if let Some(binding) = self.narrow(&name) {
+                self.declare(&name, binding);
+            }
+        }
+    }
+
+    fn statements(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Stmt) {
+        let span = &statement.span;
+        match &statement.kind {
+            StmtKind::Let { name, type_, value } => {
+                let found = value.as_ref().map(|value| self.expression(value));
+                let binding = match (type_, found) {
+                    (Some(type_), _) if !self.known_type(type_, span) => Binding::new(Ty::Unknown),
+                    (Some(type_), Some(found)) => {
+                        let declared = Ty::from(type_);
+                        if !declared.accepts(&found) {
+                            self.mismatch(type_, &found, span);
+                            Binding::new(declared)
+                        } else if !found.is_known() {
+                            Binding::new(declared)
+                        } else {
+                            Binding {
+                                declared,
+                                current: found,
+                            }
+                        }
+                    }
+                    (Some(type_), None) => {
+                        if let Type::Function { .. } | Type::Named(_) = type_ {
+                            self.error(
+                                TypeError::NoDefault {
+                                    type_: type_.to_string(),
+                                    name: name.clone(),
+                                },
+                                span,
+                            );
+                        }
+                        let declared = Ty::from(type_);
+                        let current = match declared {
+                            Ty::Optional(_) => Ty::None,
+                            _ => declared.clone(),
+                        };
+                        Binding { declared, current }
+                    }
+                    (None, Some(found)) => Binding {
+                        declared: found.widened(),
+                        current: found,
+                    },
+                    (None, None) => Binding::new(Ty::Unknown),
+                };
+                self.declare(name, binding);
+            }
+            StmtKind::Assign { name, value, .. } => {
+                let found = self.expression(value);
+                let Some(location) = self.find(name) else {
+                    return;
+                };
+                // Only an assignment in the variable's own scope is certain to
+                // have run when the code after it does
+                let innermost = match location {
+                    Some(index) => index + 1 == self.scopes.len(),
+                    None => self.scopes.is_empty(),
+                };
+                let binding = self.binding(location, name);
+                if !binding.declared.accepts(&found) {
+                    let expected = binding.declared.to_string();
+                    self.mismatch(expected, &found, span);
+                } else if innermost && found.is_known() {
+                    binding.current = found;
+                } else {
+                    binding.current = binding.declared.clone();
+                }
+            }
+            StmtKind::SetIndex {
+                target,
+                index,
+                value,
+            } => {
+                let element = self.indexed(target, index);
+                let found = self.expression(value);
+                if !element.accepts(&found) {
+                    self.mismatch(element, &found, &value.span);
+                }
+            }
+            StmtKind::SetField {
+                target,
+                name,
+                value,
+            } => {
+                let expected = self.field(target, name);
+                let found = self.expression(value);
+                if let Some((struct_, field)) = expected {
+                    if !Ty::from(&field.type_).accepts(&found) {
+                        self.field_mismatch(&struct_, &field, &found, &value.span);
+                    }
+                }
+            }
+            StmtKind::Print(expr) | StmtKind::Expression(expr) => {
+                self.expression(expr);
+            }
+            StmtKind::Struct(struct_) => {
+                self.structs
+                    .insert(struct_.name.clone(), Rc::clone(struct_));
+                for field in &struct_.fields {
+                    self.known_type(&field.type_, &field.span);
+                }
+                self.declare(
+                    &struct_.name,
+                    Binding::new(Ty::StructType(Rc::clone(struct_))),
+                );
+            }
+            StmtKind::Enum(enum_) => {
+                self.enums.insert(enum_.name.clone(), Rc::clone(enum_));
+                self.declare(&enum_.name, Binding::new(Ty::EnumType(Rc::clone(enum_))));
+                for variant in &enum_.variants {
+                    for type_ in &variant.types {
+                        self.known_type(type_, &variant.span);
+                    }
+                    let type_ = Ty::of_variant(enum_, variant);
+                    self.declare(
+                        &variant.name,
+                        Binding {
+                            declared: type_.widened(),
+                            current: type_,
+                        },
+                    );
+                }
+            }
+            StmtKind::Function(function) => {
+                let name = function.name.clone().expect("declared functions are named");
+                let type_ = Ty::of_function(function);
+                self.declare(
+                    &name,
+                    Binding {
+                        declared: type_.widened(),
+                        current: type_,
+                    },
+                );
+                self.function(function);
+            }
+            StmtKind::Return(value) => {
+                let found = match value {
+                    Some(value) => self.expression(value),
+                    None => Ty::None,
+                };
+                let Some(function) = self.functions.last() else {
+                    return;
+                };
+                let Some(type_) = &function.return_type else {
+                    return;
+                };
+                if !Ty::from(type_).accepts(&found) {
+                    let error = TypeError::ReturnMismatch {
+                        function: function.name.clone(),
+                        expected: type_.to_string(),
+                        found: Some(found.to_string()),
+                    };
+                    self.error(error, span);
+                }
+            }
+            StmtKind::Block(statements) => {
+                self.scoped(Vec::new(), |checker| checker.statements(statements));
+            }
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.condition(condition);
+                self.narrowed(not_none(condition, true), |checker| {
+                    checker.statements(then_branch)
+                });
+                match else_branch {
+                    Some(else_branch) => {
+                        self.narrowed(not_none(condition, false), |checker| {
+                            checker.statement(else_branch)
+                        });
+                    }
+                    // `if x == none { return; }` leaves `x` set afterwards
+                    None if diverges(then_branch, true) => {
+                        self.narrow_here(not_none(condition, false));
+                    }
+                    None => {}
+                }
+            }
+            StmtKind::While { condition, body } => {
+                self.condition(condition);
+                self.narrowed(not_none(condition, true), |checker| {
+                    checker.statements(body)
+                });
+            }
+            StmtKind::For {
+                key,
+                variable,
+                iterable,
+                body,
+            } => {
+                let (key_type, element) = match iterable {
+                    Iterable::Range {
+                        start, end, step, ..
+                    } => {
+                        let start = self.number(start);
+                        let end = self.number(end);
+                        let step = step.as_ref().map_or(Ty::Int, |step| self.number(step));
+                        (Ty::Unknown, start.arithmetic(&end).arithmetic(&step))
+                    }
+                    Iterable::Expr(expr) => {
+                        let found = self.expression(expr);
+                        match self.checked(found, &expr.span) {
+                            Ty::String => (Ty::Int, Ty::String),
+                            Ty::List(element) => (Ty::Int, *element),
+                            Ty::Map(key_type, _) if key.is_none() => (Ty::Unknown, *key_type),
+                            Ty::Map(key_type, value) => (*key_type, *value),
+                            Ty::Unknown => (Ty::Unknown, Ty::Unknown),
+                            found => {
+                                self.error(TypeError::NotIterable(found.to_string()), &expr.span);
+                                (Ty::Unknown, Ty::Unknown)
+                            }
+                        }
+                    }
+                };
+                let mut variables = vec![(variable.clone(), element)];
+                if let Some(key) = key {
+                    variables.push((key.clone(), key_type));
+                }
+                self.scoped(variables, |checker| checker.statements(body));
+            }
+            StmtKind::Match { value, arms } => self.match_statement(value, arms),
+            StmtKind::Break | StmtKind::Continue => {}
+        }
+    }
+
+    fn match_statement(&mut self, value: &Expr, arms: &[Arm]) {
+        let found = self.expression(value);
+        let enum_ = match self.checked(found, &value.span) {
+            Ty::Named(name) if self.enums.contains_key(&name) => {
+                Some(Rc::clone(&self.enums[&name]))
+            }
+            Ty::Unknown => self.inferred_enum(arms),
+            found => {
+                self.error(TypeError::NotAnEnum(found.to_string()), &value.span);
+                None
+            }
+        };
+
+        let mut covered = HashSet::new();
+        let mut wildcard = false;
+        for arm in arms {
+            let mut bindings = Vec::new();
+            match &arm.pattern {
+                Pattern::Wildcard => wildcard |= arm.guard.is_none(),
+                Pattern::Variant {
+                    name,
+                    bindings: names,
+                } => {
+                    let variant = enum_.as_ref().and_then(|enum_| enum_.variant(name));
+                    if let Some((index, variant)) = variant {
+                        if names.len() != variant.types.len() {
+                            self.error(
+                                TypeError::PatternMismatch {
+                                    variant: name.clone(),
+                                    expected: variant.types.len(),
+                                    found: names.len(),
+                                },
+                                &arm.span,
+                            );
+                        }
+                        if arm.guard.is_none() {
+                            covered.insert(index);
+                        }
+                    } else if enum_.is_some() || self.enums_with(name).is_empty() {
+                        self.error(
+                            TypeError::UnknownVariant {
+                                enum_: enum_.as_ref().map(|enum_| enum_.name.clone()),
+                                variant: name.clone(),
+                            },
+                            &arm.span,
+                        );
+                    }
+                    for (i, binding) in names.iter().enumerate() {
+                        let type_ = variant
+                            .and_then(|(_, variant)| variant.types.get(i))
+                            .map_or(Ty::Unknown, Ty::from);
+                        if let Some(binding) = binding {
+                            bindings.push((binding.clone(), type_));
+                        }
+                    }
+                }
+            }
+            self.scoped(bindings, |checker| match &arm.guard {
+                Some(guard) => {
+                    checker.condition(guard);
+                    checker.narrowed(not_none(guard, true), |checker| {
+                        checker.statements(&arm.body)
+                    });
+                }
+                None => checker.statements(&arm.body),
+            });
+        }
+
+        let Some(enum_) = enum_ else {
+            return;
+        };
+        let missing: Vec<String> = enum_
+            .variants
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !covered.contains(index))
+            .map(|(_, variant)| variant.name.clone())
+            .collect();
+        if !wildcard && !missing.is_empty() {
+            self.error(TypeError::NonExhaustiveMatch(missing), &value.span);
+        }
+    }
+
+    /// The enums declared with a variant named `name`.
+    fn enums_with(&self, name: &str) -> Vec<Rc<Enum>> {
+        self.enums
+            .values()
+            .filter(|enum_| enum_.variant(name).is_some())
+            .cloned()
+            .collect()
+    }
+
+    /// The enum a value of unknown type is matched as, going by the first
+    /// variant named in the patterns, if only one enum has it.
+    fn inferred_enum(&self, arms: &[Arm]) -> Option<Rc<Enum>> {
+        let name = arms.iter().find_map(|arm| match &arm.pattern {
+            Pattern::Variant { name, .. } => Some(name),
+            Pattern::Wildcard => None,
+        })?;
+        let mut enums = self.enums_with(name);
+        if enums.len() == 1 {
+            enums.pop()
+        } else {
+            None
+        }
+    }
+
+    fn function(&mut self, function: &Function) {
+        let name = function.name.as_deref().unwrap_or("anonymous").to_string();
+        for param in &function.params {
+            if let Some(type_) = &param.type_ {
+                self.known_type(type_, &param.span);
+            }
+        }
+        if let Some(type_) = &function.return_type {
+            self.known_type(type_, &function.span);
+        }
+        let params = function
+            .params
+            .iter()
+            .map(|param| (param.name.clone(), Ty::annotated(&param.type_)))
+            .collect();
+
+        self.functions.push(FunctionContext {
+            name: name.clone(),
+            return_type: function.return_type.clone(),
+            scope: self.scopes.len(),
+        });
+        self.scoped(params, |checker| checker.statements(&function.body));
+        self.functions.pop();
+
+        // Reaching the end of the body returns `none`
+        if let Some(type_) = &function.return_type {
+            if !matches!(type_, Type::Optional(_)) && !diverges(&function.body, false) {
+                self.error(
+                    TypeError::ReturnMismatch {
+                        function: name,
+                        expected: type_.to_string(),
+                        found: None,
+                    },
+                    &function.span,
+                );
+            }
+        }
+    }
+
+    fn condition(&mut self, condition: &Expr) {
+        let found = self.expression(condition);
+        let found = self.checked(found, &condition.span);
+        if !Ty::Bool.accepts(&found) {
+            self.mismatch("Bool", &found, &condition.span);
+        }
+    }
+
+    /// Checks an expression that must be an int or a float, and returns its
+    /// type.
+    fn number(&mut self, expr: &Expr) -> Ty {
+        let found = self.expression(expr);
+        let found = self.checked(found, &expr.span);
+        if !Ty::Number.accepts(&found) {
+            self.mismatch("Number", &found, &expr.span);
+        }
+        found
+    }
+
+    fn index(&mut self, expr: &Expr) {
+        let found = self.expression(expr);
+        let found = self.checked(found, &expr.span);
+        if !Ty::Int.accepts(&found) {
+            self.mismatch("Int", &found, &expr.span);
+        }
+    }
+
+    /// The struct and field `target.name` refers to, if they are known.
+    fn field(&mut self, target: &Expr, name: &str) -> Option<(Rc<Struct>, Field)> {
+        let found = self.expression(target);
+        let found = self.checked(found, &target.span);
+        self.field_of(found, target, name)
+    }
+
+    /// Like `field`, for a target whose type is `found`.
+    fn field_of(&mut self, found: Ty, target: &Expr, name: &str) -> Option<(Rc<Struct>, Field)> {
+        let error = match found {
+            Ty::Named(struct_name) if self.structs.contains_key(&struct_name) => {
+                let struct_ = Rc::clone(&self.structs[&struct_name]);
+                match struct_.field(name) {
+                    Some((_, field)) => {
+                        let field = field.clone();
+                        return Some((struct_, field));
+                    }
+                    None => TypeError::UnknownField {
+                        type_: struct_name,
+                        field: name.to_string(),
+                    },
+                }
+            }
+            Ty::Unknown => return None,
+            // Reading a member is handled by `member`, so this is an
+            // assignment
+            Ty::Module(module) => {
+                let member = format!("{}.{}", module, name);
+                self.error(SyntaxError::NotAssignable(member), &target.span);
+                return None;
+            }
+            found => TypeError::UnknownField {
+                type_: found.to_string(),
+                field: name.to_string(),
+            },
+        };
+        self.error(error, &target.span);
+        None
+    }
+
+    /// The type of the member `name` of a module.
+    fn member(&mut self, module: &str, name: &str, span: &Span) -> Ty {
+        let module = builtins::module(module).expect("module types are only made for modules");
+        match module.member(name) {
+            Some(Value::Builtin(builtin)) => Ty::Builtin(builtin.name),
+            Some(_) => Ty::Float,
+            None => {
+                self.error(
+                    TypeError::UnknownMember {
+                        module: module.name.to_string(),
+                        member: name.to_string(),
+                    },
+                    span,
+                );
+                Ty::Unknown
+            }
+        }
+    }
+
+    fn field_mismatch(&mut self, struct_: &Struct, field: &Field, found: &Ty, span: &Span) {
+        self.error(
+            TypeError::FieldMismatch {
+                struct_: struct_.name.clone(),
+                field: field.name.clone(),
+                expected: field.type_.to_string(),
+                found: found.to_string(),
+            },
+            span,
+        );
+    }
+
+    /// The type of `target[index]`, an element of a list or a value of a map.
+    fn indexed(&mut self, target: &Expr, index: &Expr) -> Ty {
+        let found = self.expression(target);
+        match self.checked(found, &target.span) {
+            Ty::List(element) => {
+                self.index(index);
+                *element
+            }
+            Ty::Map(key, value) => {
+                let found = self.expression(index);
+                let found = self.checked(found, &index.span);
+                if !key.accepts(&found) {
+                    self.mismatch(key, &found, &index.span);
+                }
+                *value
+            }
+            found => {
+                if found != Ty::Unknown {
+                    self.error(TypeError::NotIndexable(found.to_string()), &target.span);
+                }
+                self.expression(index);
+                Ty::Unknown
+            }
+        }
+    }
+
+    /// The type of an operand of `op`, which must not be optional.
+    fn operand(&mut self, expr: &Expr, op: impl ToString) -> Ty {
+        let found = self.expression(expr);
+        let found = self.checked(found, &expr.span);
+        if let Ty::None = found {
+            self.error(
+                TypeError::InvalidOperand {
+                    op: op.to_string(),
+                    operand: found.to_string(),
+                },
+                &expr.span,
+            );
+            return Ty::Unknown;
+        }
+        found
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Ty {
+        let span = &expr.span;
+        match &expr.kind {
+            ExprKind::Int(_) => Ty::Int,
+            ExprKind::Float(_) => Ty::Float,
+            ExprKind::String(_) => Ty::String,
+            ExprKind::Interpolation(parts) => {
+                for part in parts {
+                    self.expression(part);
+                }
+                Ty::String
+            }
+            ExprKind::Bool(_) => Ty::Bool,
+            ExprKind::None => Ty::None,
+            ExprKind::Variable { name, .. } => self.type_of(name),
+            ExprKind::Unary { op, expr } => {
+                let found = self.operand(expr, op);
+                let result = match (op, &found) {
+                    (UnaryOp::Negate, Ty::Int | Ty::Float) => found.clone(),
+                    (UnaryOp::Negate, _) => Ty::Number,
+                    (UnaryOp::Not, _) => Ty::Bool,
+                };
+                if !result.accepts(&found) {
+                    self.error(
+                        TypeError::InvalidOperand {
+                            op: op.to_string(),
+                            operand: found.to_string(),
+                        },
+                        span,
+                    );
+                }
+                result
+            }
+            ExprKind::Binary {
+                left,
+                op: op @ (BinaryOp::Equal | BinaryOp::NotEqual),
+                right,
+            } => {
+                let left = self.expression(left);
+                let right = self.expression(right);
+                if !left.comparable(&right) {
+                    self.error(
+                        TypeError::InvalidOperands {
+                            op: op.to_string(),
+                            left: left.to_string(),
+                            right: right.to_string(),
+                        },
+                        span,
+                    );
+                }
+                Ty::Bool
+            }
+            ExprKind::Binary { left, op, right } => {
+                let left = self.operand(left, op);
+                let right = self.operand(right, op);
+                self.binary(left, *op, right, span)
+            }
+            ExprKind::Logical { left, op, right } => {
+                self.logical_operand(left, *op);
+                // The right side only runs if the left is true for `and`,
+                // false for `or`
+                let outcome = *op == LogicalOp::And;
+                self.narrowed(not_none(left, outcome), |checker| {
+                    checker.logical_operand(right, *op)
+                });
+                Ty::Bool
+            }
+            ExprKind::Call { callee, args } => self.call(callee, args, span),
+            ExprKind::Function(function) => {
+                self.function(function);
+                Ty::of_function(function)
+            }
+            ExprKind::Struct { name, fields, .. } => {
+                let struct_ = match self.type_of(name) {
+                    Ty::StructType(struct_) => Some(struct_),
+                    Ty::Unknown => self.structs.get(name).cloned(),
+                    _ => {
+                        self.error(TypeError::NotAStruct(name.clone()), span);
+                        None
+                    }
+                };
+                let types: Vec<Ty> = fields
+                    .iter()
+                    .map(|field| self.expression(&field.value))
+                    .collect();
+                let Some(struct_) = struct_ else {
+                    return Ty::Unknown;
+                };
+
+                for (field_value, found) in fields.iter().zip(types) {
+                    match struct_.field(&field_value.name) {
+                        Some((_, field)) if !Ty::from(&field.type_).accepts(&found) => {
+                            self.field_mismatch(&struct_, field, &found, &field_value.value.span);
+                        }
+                        Some(_) => {}
+                        None => self.error(
+                            TypeError::UnknownField {
+                                type_: name.clone(),
+                                field: field_value.name.clone(),
+                            },
+                            &field_value.span,
+                        ),
+                    }
+                }
+                for field in &struct_.fields {
+                    if !fields.iter().any(|value| value.name == field.name) {
+                        self.error(
+                            TypeError::MissingField {
+                                struct_: name.clone(),
+                                field: field.name.clone(),
+                            },
+                            span,
+                        );
+                    }
+                }
+                Ty::Named(struct_.name.clone())
+            }
+            ExprKind::Field { target, name } => {
+                let found = self.expression(target);
+                match self.checked(found, &target.span) {
+                    Ty::Module(module) => self.member(module, name, span),
+                    found => match self.field_of(found, target, name) {
+                        Some((_, field)) => Ty::from(&field.type_),
+                        None => Ty::Unknown,
+                    },
+                }
+            }
+            ExprKind::List(elements) => {
+                let element = elements
+                    .iter()
+                    .map(|element| self.expression(element))
+                    .reduce(|left, right| left.join(&right))
+                    .unwrap_or(Ty::Unknown);
+                Ty::List(Box::new(element))
+            }
+            ExprKind::Map(entries) => {
+                let mut key_type: Option<Ty> = None;
+                let mut value_type: Option<Ty> = None;
+                for (key, value) in entries {
+                    let found = self.expression(key);
+                    if !found.is_key() {
+                        self.error(TypeError::InvalidKey(found.to_string()), &key.span);
+                    }
+                    key_type = Some(key_type.map_or(found.clone(), |type_| type_.join(&found)));
+                    let found = self.expression(value);
+                    value_type = Some(value_type.map_or(found.clone(), |type_| type_.join(&found)));
+                }
+                Ty::Map(
+                    Box::new(key_type.unwrap_or(Ty::Unknown)),
+                    Box::new(value_type.unwrap_or(Ty::Unknown)),
+                )
+            }
+            ExprKind::Index { target, index } => self.indexed(target, index),
+            ExprKind::Slice {
+                target, start, end, ..
+            } => {
+                let found = self.expression(target);
+                let element = match self.checked(found, &target.span) {
+                    Ty::List(element) => *element,
+                    Ty::Unknown => Ty::Unknown,
+                    found => {
+                        self.error(TypeError::NotSliceable(found.to_string()), &target.span);
+                        Ty::Unknown
+                    }
+                };
+                for bound in [start, end].into_iter().flatten() {
+                    self.index(bound);
+                }
+                Ty::List(Box::new(element))
+            }
+        }
+    }
+
+    fn logical_operand(&mut self, expr: &Expr, op: LogicalOp) {
+        let found = self.operand(expr, op);
+        if !Ty::Bool.accepts(&found) {
+            self.error(
+                TypeError::InvalidOperand {
+                    op: op.to_string(),
+                    operand: found.to_string(),
+                },
+                &expr.span,
+            );
+        }
+    }
+
+    /// The type of an arithmetic or comparison expression, whose operands
+    /// have been checked not to be optional.
+    fn binary(&mut self, left: Ty, op: BinaryOp, right: Ty, span: &Span) -> Ty {
+        let both = |type_: Ty| type_.accepts(&left) && type_.accepts(&right);
+        let result = match op {
+            BinaryOp::Equal | BinaryOp::NotEqual => Some(Ty::Bool),
+            BinaryOp::Add => match (&left, &right) {
+                (Ty::Unknown, Ty::Unknown) => Some(Ty::Unknown),
+                _ if both(Ty::Number) => Some(left.arithmetic(&right)),
+                _ if both(Ty::String) => Some(Ty::String),
+                _ => None,
+            },
+            BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo => {
+                both(Ty::Number).then(|| left.arithmetic(&right))
+            }
+            BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual => {
+                (both(Ty::Number) || both(Ty::String)).then_some(Ty::Bool)
+            }
+        };
+
+        result.unwrap_or_else(|| {
+            let error = match (&left, &right) {
+                (Ty::Unknown, operand) | (operand, Ty::Unknown) => TypeError::InvalidOperand {
+                    op: op.to_string(),
+                    operand: operand.to_string(),
+                },
+                (left, right) => TypeError::InvalidOperands {
+                    op: op.to_string(),
+                    left: left.to_string(),
+                    right: right.to_string(),
+                },
+            };
+            self.error(error, span);
+            Ty::Unknown
+        })
+    }
+
+    /// Checks a call, where `span` is the span of the call expression, and
+    /// returns the type of its result.
+    fn call(&mut self, callee: &Expr, args: &[Expr], span: &Span) -> Ty {
+        let callee_type = self.expression(callee);
+        let callee_type = self.checked(callee_type, &callee.span);
+        let arg_types: Vec<Ty> = args.iter().map(|arg| self.expression(arg)).collect();
+
+        let signature = match callee_type {
+            Ty::Function(Some(signature)) => signature,
+            Ty::Builtin(name) => return self.builtin(builtin(name), args, arg_types, span),
+            Ty::Function(None) | Ty::Unknown => return Ty::Unknown,
+            found => {
+                self.error(TypeError::NotCallable(found.to_string()), &callee.span);
+                return Ty::Unknown;
+            }
+        };
+        let function = match (&signature.name, &callee.kind) {
+            (Some(name), _) | (None, ExprKind::Variable { name, .. }) => name.clone(),
+            (None, _) => "anonymous".to_string(),
+        };
+
+        if args.len() != signature.params.len() {
+            self.error(
+                TypeError::ArityMismatch {
+                    function,
+                    expected: signature.params.len(),
+                    found: args.len(),
+                },
+                span,
+            );
+            return signature.return_type.clone();
+        }
+
+        for ((param, expected), (arg, found)) in
+            signature.params.iter().zip(args.iter().zip(arg_types))
+        {
+            if expected.accepts(&found) {
+                continue;
+            }
+            match param {
+                Some(param) => self.error(
+                    TypeError::ArgumentMismatch {
+                        function: function.clone(),
+                        param: param.clone(),
+                        expected: expected.to_string(),
+                        found: found.to_string(),
+                    },
+                    &arg.span,
+                ),
+                None => self.mismatch(expected, &found, &arg.span),
+            }
+        }
+        signature.return_type.clone()
+    }
+
+    /// Checks a call of a builtin and returns the type of its result.
+    fn builtin(
+        &mut self,
+        builtin: &'static Builtin,
+        args: &[Expr],
+        arg_types: Vec<Ty>,
+        span: &Span,
+    ) -> Ty {
+        if args.len() != builtin.params.len() {
+            self.error(
+                TypeError::ArityMismatch {
+                    function: builtin.name.to_string(),
+                    expected: builtin.params.len(),
+                    found: args.len(),
+                },
+                span,
+            );
+            return Ty::Unknown;
+        }
+
+        match builtin.name {
+            "len" => {
+                let found = self.checked(arg_types[0].clone(), &args[0].span);
+                if !matches!(found, Ty::List(_) | Ty::Map(..) | Ty::String | Ty::Unknown) {
+                    let expected = "List, Map or String";
+                    self.argument_mismatch(builtin, 0, expected, &found, &args[0].span);
+                }
+                Ty::Int
+            }
+            "push" => {
+                let element = self.list_argument(builtin, &args[0], &arg_types[0]);
+                self.argument(builtin, 1, &args[1], &arg_types[1], &element);
+                Ty::None
+            }
+            "pop" => self.list_argument(builtin, &args[0], &arg_types[0]),
+            "insert" => {
+                let element = self.list_argument(builtin, &args[0], &arg_types[0]);
+                self.argument(builtin, 1, &args[1], &arg_types[1], &Ty::Int);
+                self.argument(builtin, 2, &args[2], &arg_types[2], &element);
+                Ty::None
+            }
+            "remove" | "contains" => {
+                let found = self.checked(arg_types[0].clone(), &args[0].span);
+                let (key, value) = match found {
+                    Ty::List(element) if builtin.name == "remove" => (Ty::Int, *element),
+                    Ty::List(element) => (*element, Ty::Unknown),
+                    Ty::Map(key, value) => (*key, *value),
+                    Ty::String if builtin.name == "contains" => (Ty::String, Ty::Unknown),
+                    Ty::Unknown => (Ty::Unknown, Ty::Unknown),
+                    found => {
+                        let expected = if builtin.name == "remove" {
+                            "List or Map"
+                        } else {
+                            "List, Map or String"
+                        };
+                        self.argument_mismatch(builtin, 0, expected, &found, &args[0].span);
+                        (Ty::Unknown, Ty::Unknown)
+                    }
+                };
+                self.argument(builtin, 1, &args[1], &arg_types[1], &key);
+                if builtin.name == "remove" {
+                    value
+                } else {
+                    Ty::Bool
+                }
+            }
+            "get" => {
+                let (key, value) = self.map_argument(builtin, &args[0], &arg_types[0]);
+                self.argument(builtin, 1, &args[1], &arg_types[1], &key);
+                match value {
+                    Ty::Unknown | Ty::None | Ty::Optional(_) => value,
+                    value => Ty::Optional(Box::new(value)),
+                }
+            }
+            "keys" => {
+                let (key, _) = self.map_argument(builtin, &args[0], &arg_types[0]);
+                Ty::List(Box::new(key))
+            }
+            "values" => {
+                let (_, value) = self.map_argument(builtin, &args[0], &arg_types[0]);
+                Ty::List(Box::new(value))
+            }
+            "upper" | "lower" | "trim" | "replace" | "substring" | "repeat" => {
+                let params = match builtin.name {
+                    "replace" => vec![Ty::String, Ty::String, Ty::String],
+                    "substring" => vec![Ty::String, Ty::Int, Ty::Int],
+                    "repeat" => vec![Ty::String, Ty::Int],
+                    _ => vec![Ty::String],
+                };
+                self.arguments(builtin, args, &arg_types, &params);
+                Ty::String
+            }
+            "chars" | "split" => {
+                let params = vec![Ty::String; builtin.params.len()];
+                self.arguments(builtin, args, &arg_types, &params);
+                Ty::List(Box::new(Ty::String))
+            }
+            "join" => {
+                self.list_argument(builtin, &args[0], &arg_types[0]);
+                self.argument(builtin, 1, &args[1], &arg_types[1], &Ty::String);
+                Ty::String
+            }
+            "starts_with" | "ends_with" => {
+                self.arguments(builtin, args, &arg_types, &[Ty::String, Ty::String]);
+                Ty::Bool
+            }
+            "find" => {
+                self.arguments(builtin, args, &arg_types, &[Ty::String, Ty::String]);
+                Ty::Optional(Box::new(Ty::Int))
+            }
+            "math.abs" | "math.min" | "math.max" | "math.clamp" => {
+                self.number_arguments(builtin, args, &arg_types)
+            }
+            "math.floor" | "math.ceil" | "math.round" => {
+                self.number_arguments(builtin, args, &arg_types);
+                Ty::Int
+            }
+            // A negative exponent gives a float even for ints
+            "math.pow" => match self.number_arguments(builtin, args, &arg_types) {
+                Ty::Int => Ty::Number,
+                type_ => type_,
+            },
+            name if name.starts_with("math.") => {
+                self.number_arguments(builtin, args, &arg_types);
+                Ty::Float
+            }
+            "to_int" | "to_float" => {
+                let found = self.checked(arg_types[0].clone(), &args[0].span);
+                if !found.is_number() && !matches!(found, Ty::String | Ty::Unknown) {
+                    let expected = "Int, Float or String";
+                    self.argument_mismatch(builtin, 0, expected, &found, &args[0].span);
+                }
+                if builtin.name == "to_int" {
+                    Ty::Int
+                } else {
+                    Ty::Float
+                }
+            }
+            _ => Ty::Unknown,
+        }
+    }
+
+    /// Checks the first argument of a builtin that takes a list, and returns
+    /// the type of its elements.
+    fn list_argument(&mut self, builtin: &Builtin, arg: &Expr, found: &Ty) -> Ty {
+        match self.checked(found.clone(), &arg.span) {
+            Ty::List(element) => *element,
+            Ty::Unknown => Ty::Unknown,
+            found => {
+                self.argument_mismatch(builtin, 0, "List", &found, &arg.span);
+                Ty::Unknown
+            }
+        }
+    }
+
+    /// Checks the first argument of a builtin that takes a map, and returns
+    /// the types of its keys and values.
+    fn map_argument(&mut self, builtin: &Builtin, arg: &Expr, found: &Ty) -> (Ty, Ty) {
+        match self.checked(found.clone(), &arg.span) {
+            Ty::Map(key, value) => (*key, *value),
+            Ty::Unknown => (Ty::Unknown, Ty::Unknown),
+            found => {
+                self.argument_mismatch(builtin, 0, "Map", &found, &arg.span);
+                (Ty::Unknown, Ty::Unknown)
+            }
+        }
+    }
+
+    fn argument(&mut self, builtin: &Builtin, i: usize, arg: &Expr, found: &Ty, expected: &Ty) {
+        let found = match expected {
+            Ty::Optional(_) | Ty::Unknown => found.clone(),
+            _ => self.checked(found.clone(), &arg.span),
+        };
+        if !expected.accepts(&found) {
+            self.argument_mismatch(builtin, i, expected, &found, &arg.span);
+        }
+    }
+
+    /// Checks arguments that must all be numbers, and returns the type of
+    /// arithmetic on them.
+    fn number_arguments(&mut self, builtin: &Builtin, args: &[Expr], found: &[Ty]) -> Ty {
+        self.arguments(builtin, args, found, &vec![Ty::Number; args.len()]);
+        found
+            .iter()
+            .map(|found| match found {
+                Ty::Int | Ty::Float => found.clone(),
+                _ => Ty::Number,
+            })
+            .reduce(|left, right| left.arithmetic(&right))
+            .unwrap_or(Ty::Number)
+    }
+
+    /// Checks every argument against the type of its parameter.
+    fn arguments(&mut self, builtin: &Builtin, args: &[Expr], found: &[Ty], expected: &[Ty]) {
+        for (i, expected) in expected.iter().enumerate() {
+            self.argument(builtin, i, &args[i], &found[i], expected);
+        }
+    }
+
+    fn argument_mismatch(
+        &mut self,
+        builtin: &Builtin,
+        i: usize,
+        expected: impl ToString,
+        found: &Ty,
+        span: &Span,
+    ) {
+        self.error(
+            TypeError::ArgumentMismatch {
+                function: builtin.name.to_string(),
+                param: builtin.params[i].to_string(),
+                expected: expected.to_string(),
+                found: found.to_string(),
+            },
+            span,
+        );
+    }
+}
+
+/// The variables `condition` shows are not `none` when it evaluates to
+/// `outcome`, from comparisons such as `x != none` and `x == none`.
+fn not_none(condition: &Expr, outcome: bool) -> Vec<String> {
+    match &condition.kind {
+        ExprKind::Binary {
+            left,
+            op: op @ (BinaryOp::Equal | BinaryOp::NotEqual),
+            right,
+        } if (*op == BinaryOp::NotEqual) == outcome => match (&left.kind, &right.kind) {
+            (ExprKind::Variable { name, .. }, ExprKind::None)
+            | (ExprKind::None, ExprKind::Variable { name, .. }) => vec![name.clone()],
+            _ => Vec::new(),
+        },
+        ExprKind::Unary {
+            op: UnaryOp::Not,
+            expr,
+        } => not_none(expr, !outcome),
+        ExprKind::Logical { left, op, right } => match (op, outcome) {
+            // Both sides ran and gave `outcome`
+            (LogicalOp::And, true) | (LogicalOp::Or, false) => {
+                let mut names = not_none(left, outcome);
+                names.extend(not_none(right, outcome));
+                names
+            }
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// Whether running `statements` never reaches their end because every path
+/// returns, or with `loop_exits`, also breaks or continues.
+fn diverges(statements: &[Stmt], loop_exits: bool) -> bool {
+    statements.iter().any(|statement| match &statement.kind {
+        StmtKind::Return(_) => true,
+        StmtKind::Break | StmtKind::Continue => loop_exits,
+        StmtKind::Block(statements) => diverges(statements, loop_exits),
+        StmtKind::If {
+            then_branch,
+            else_branch: Some(else_branch),
+            ..
+        } => {
+            diverges(then_branch, loop_exits)
+                && diverges(std::slice::from_ref(else_branch), loop_exits)
+        }
+        StmtKind::While { condition, body } => {
+            matches!(condition.kind, ExprKind::Bool(true)) && !breaks(body)
+        }
+        // Matches that are not exhaustive are reported, so one arm runs
+        StmtKind::Match { arms, .. } => {
+            !arms.is_empty() && arms.iter().all(|arm| diverges(&arm.body, loop_exits))
+        }
+        _ => false,
+    })
+}
+
+fn builtin(name: &str) -> &'static Builtin {
+    builtins::get(name).expect("builtin types are only made for builtins")
+}
+
+/// Calls `found` with the name of every variable a function in `statements`
+/// assigns although it is declared outside that function, and whether it is
+/// a global. `scopes` is how many scopes of the innermost function around
+/// `statements` are open, counted like the resolver does, or `None` outside
+/// functions.
+fn assignments(statements: &[Stmt], scopes: Option<usize>, found: &mut impl FnMut(&str, bool)) {
+    let inner = scopes.map(|scopes| scopes + 1);
+    for statement in statements {
+        match &statement.kind {
+            StmtKind::Let { value, .. } => {
+                if let Some(value) = value {
+                    expression_assignments(value, found);
+                }
+            }
+            StmtKind::Assign { name, value, depth } => {
+                expression_assignments(value, found);
+                match (scopes, depth) {
+                    (Some(_), None) => found(name, true),
+                    (Some(scopes), Some(depth)) if *depth >= scopes => found(name, false),
+                    _ => {}
+                }
+            }
+            StmtKind::SetIndex {
+                target,
+                index,
+                value,
+            } => {
+                for expr in [target, index, value] {
+                    expression_assignments(expr, found);
+                }
+            }
+            StmtKind::SetField { target, value, .. } => {
+                expression_assignments(target, found);
+                expression_assignments(value, found);
+            }
+            StmtKind::Print(expr) | StmtKind::Expression(expr) => {
+                expression_assignments(expr, found);
+            }
+            StmtKind::Return(value) => {
+                if let Some(value) = value {
+                    expression_assignments(value, found);
+                }
+            }
+            StmtKind::Function(function) => assignments(&function.body, Some(1), found),
+            StmtKind::Block(body) => assignments(body, inner, found),
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                expression_assignments(condition, found);
+                assignments(then_branch, inner, found);
+                if let Some(else_branch) = else_branch {
+                    assignments(std::slice::from_ref(else_branch), scopes, found);
+                }
+            }
+            StmtKind::While { condition, body } => {
+                expression_assignments(condition, found);
+                assignments(body, inner, found);
+            }
+            StmtKind::For { iterable, body, .. } => {
+                match iterable {
+                    Iterable::Range {
+                        start, end, step, ..
+                    } => {
+                        for expr in [start, end].into_iter().chain(step.as_deref()) {
+                            expression_assignments(expr, found);
+                        }
+                    }
+                    Iterable::Expr(expr) => expression_assignments(expr, found),
+                }
+                assignments(body, inner, found);
+            }
+            StmtKind::Match { value, arms } => {
+                expression_assignments(value, found);
+                for arm in arms {
+                    if let Some(guard) = &arm.guard {
+                        expression_assignments(guard, found);
+                    }
+                    assignments(&arm.body, inner, found);
+                }
+            }
+            StmtKind::Struct(_) | StmtKind::Enum(_) | StmtKind::Break | StmtKind::Continue => {}
+        }
+    }
+}
+
+/// Like `assignments`, for the functions in `expr`.
+fn expression_assignments(expr: &Expr, found: &mut impl FnMut(&str, bool)) {
+    match &expr.kind {
+        ExprKind::Int(_)
+        | ExprKind::Float(_)
+        | ExprKind::String(_)
+        | ExprKind::Bool(_)
+        | ExprKind::None
+        | ExprKind::Variable { .. } => {}
+        ExprKind::Function(function) => assignments(&function.body, Some(1), found),
+        ExprKind::Unary { expr, .. } | ExprKind::Field { target: expr, .. } => {
+            expression_assignments(expr, found);
+        }
+        ExprKind::Binary { left, right, .. }
+        | ExprKind::Logical { left, right, .. }
+        | ExprKind::Index {
+            target: left,
+            index: right,
+        } => {
+            expression_assignments(left, found);
+            expression_assignments(right, found);
+        }
+        ExprKind::Call { callee, args } => {
+            expression_assignments(callee, found);
+            for arg in args {
+                expression_assignments(arg, found);
+            }
+        }
+        ExprKind::Struct { fields, .. } => {
+            for field in fields {
+                expression_assignments(&field.value, found);
+            }
+        }
+        ExprKind::List(elements) | ExprKind::Interpolation(elements) => {
+            for element in elements {
+                expression_assignments(element, found);
+            }
+        }
+        ExprKind::Map(entries) => {
+            for (key, value) in entries {
+                expression_assignments(key, found);
+                expression_assignments(value, found);
+            }
+        }
+        ExprKind::Slice {
+            target, start, end, ..
+        } => {
+            expression_assignments(target, found);
+            for bound in [start, end].into_iter().flatten() {
+                expression_assignments(bound, found);
+            }
+        }
+    }
+}
+
+/// Whether `statements` contain a `break` out of the loop they are the body of.
+fn breaks(statements: &[Stmt]) -> bool {
+    statements.iter().any(|statement| match &statement.kind {
+        StmtKind::Break => true,
+        StmtKind::Block(statements) => breaks(statements),
+        StmtKind::If {
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            breaks(then_branch)
+                || else_branch
+                    .as_ref()
+                    .is_some_and(|else_branch| breaks(std::slice::from_ref(else_branch)))
+        }
+        StmtKind::Match { arms, .. } => arms.iter().any(|arm| breaks(&arm.body)),
+        // A nested loop's `break` only leaves that loop
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{ErrorKind, TypeError};
+
+    /// The kinds of the errors found in `source`, in the order found.
+    fn errors(source: &str) -> Vec<ErrorKind> {
+        match crate::check("test.fun", source) {
+            Ok(_) => Vec::new(),
+            Err(errors) => errors.iter().map(|error| error.kind().clone()).collect(),
+        }
+    }
+
+    fn unchecked(type_: &str) -> ErrorKind {
+        TypeError::UncheckedOptional(type_.to_string()).into()
+    }
+
+    #[test]
+    fn optional_must_be_checked() {
+        let source = "fn f(x: number?) { print x + 1; }";
+        assert_eq!(errors(source), [unchecked("Number?")]);
+    }
+
+    #[test]
+    fn narrows_after_not_none() {
+        let source = "
+            fn f(x: number?) {
+                if x != none {
+                    print x + 1;
+                }
+                if x == none {
+                    print 0;
+                } else {
+                    print x + 1;
+                }
+            }
+        ";
+        assert_eq!(errors(source), []);
+    }
+
+    #[test]
+    fn narrows_after_early_return() {
+        let source = "
+            fn f(x: number?): number {
+                if x == none {
+                    return 0;
+                }
+                return x + 1;
+            }
+        ";
+        assert_eq!(errors(source), []);
+    }
+
+    #[test]
+    fn does_not_narrow_without_early_return() {
+        let source = "
+            fn f(x: number?): number {
+                if x == none {
+                    print 0;
+                }
+                return x + 1;
+            }
+        ";
+        assert_eq!(errors(source), [unchecked("Number?")]);
+    }
+
+    #[test]
+    fn reassignment_undoes_narrowing() {
+        let source = "
+            fn f(x: number?) {
+                if x != none {
+                    x = none;
+                    print x + 1;
+                }
+            }
+        ";
+        let expected = TypeError::InvalidOperand {
+            op: "+".to_string(),
+            operand: "None".to_string(),
+        };
+        assert_eq!(errors(source), [expected.into()]);
+    }
+
+    #[test]
+    fn reassignment_in_nested_block_undoes_narrowing() {
+        let source = "
+            fn f(x: number?, reset: bool) {
+                if x != none {
+                    if reset {
+                        x = none;
+                    }
+                    print x + 1;
+                }
+            }
+        ";
+        assert_eq!(errors(source), [unchecked("Number?")]);
+    }
+
+    #[test]
+    fn closures_do_not_see_narrowing() {
+        let source = "
+            fn f(x: number?) {
+                if x != none {
+                    let g = fn () {
+                        return x + 1;
+                    };
+                }
+            }
+        ";
+        assert_eq!(errors(source), [unchecked("Number?")]);
+    }
+
+    #[test]
+    fn assignment_in_closure_undoes_narrowing() {
+        let source = "
+            fn f(x: number?) {
+                if x != none {
+                    let reset = fn () {
+                        x = none;
+                    };
+                    reset();
+                    print x + 1;
+                }
+            }
+        ";
+        assert_eq!(errors(source), [unchecked("Number?")]);
+    }
+
+    #[test]
+    fn assignment_in_function_prevents_narrowing_of_global() {
+        let source = "
+            let x: number? = 3;
+            fn clear() {
+                x = none;
+            }
+            if x != none {
+                clear();
+                print x + 1;
+            }
+        ";
+        assert_eq!(errors(source), [unchecked("Number?")]);
+    }
+
+    #[test]
+    fn assignment_in_earlier_closure_prevents_narrowing() {
+        let source = "
+            fn f(x: number?) {
+                let clear = fn () {
+                    x = none;
+                };
+                if x != none {
+                    clear();
+                    print x + 1;
+                }
+            }
+        ";
+        assert_eq!(errors(source), [unchecked("Number?")]);
+    }
+
+    #[test]
+    fn assignment_to_own_local_keeps_narrowing() {
+        let source = "
+            let x: number? = 3;
+            fn count() {
+                let x = 0;
+                x = x + 1;
+            }
+            if x != none {
+                count();
+                print x + 1;
+            }
+        ";
+        assert_eq!(errors(source), []);
+    }
+
+    #[test]
+    fn empty_list_can_be_pushed_to() {
+        let source = "
+            let xs = [];
+            push(xs, 1);
+            print xs[0] + 1;
+        ";
+        assert_eq!(errors(source), []);
+    }
+
+    #[test]
+    fn number_accepts_ints_and_floats() {
+        let source = "
+            fn half(x: number): number {
+                return x / 2;
+            }
+            let n: number = 1;
+            n = 2.5;
+            print half(1) + half(n);
+            let xs: list<number> = [1, 2.5];
+        ";
+        assert_eq!(errors(source), []);
+    }
+
+    #[test]
+    fn higher_order_calls() {
+        let source = "
+            fn apply(f, x) {
+                return f(x);
+            }
+            fn twice(f: fn(number): number, x: number): number {
+                return f(f(x));
+            }
+            print apply(fn (n) { return n * 2; }, 3);
+            print twice(fn (n) { return n + 1; }, 1);
+            print apply(len, [1, 2]);
+        ";
+        assert_eq!(errors(source), []);
+    }
+
+    #[test]
+    fn argument_mismatch() {
+        let source = "
+            fn double(x: number): number {
+                return x * 2;
+            }
+            print double(\"Fun\");
+        ";
+        let expected = TypeError::ArgumentMismatch {
+            function: "double".to_string(),
+            param: "x".to_string(),
+            expected: "Number".to_string(),
+            found: "String".to_string(),
+        };
+        assert_eq!(errors(source), [expected.into()]);
+    }
+
+    const SHAPE: &str = "
+        enum Shape {
+            Circle(number),
+            Rect(number, number),
+            Empty,
+        }
+    ";
+
+    fn non_exhaustive(missing: &[&str]) -> ErrorKind {
+        TypeError::NonExhaustiveMatch(missing.iter().map(|name| name.to_string()).collect()).into()
+    }
+
+    #[test]
+    fn match_covering_every_variant() {
+        let source = "
+            fn f(shape: Shape) {
+                match shape {
+                    Rect(w, h) => print w * h;
+                    Empty => print 0;
+                    Circle(_) => print 1;
+                }
+            }
+        ";
+        assert_eq!(errors(&format!("{}{}", SHAPE, source)), []);
+    }
+
+    #[test]
+    fn match_missing_variants() {
+        let source = "
+            fn f(shape: Shape) {
+                match shape {
+                    Rect(w, h) => print w * h;
+                }
+            }
+        ";
+        assert_eq!(
+            errors(&format!("{}{}", SHAPE, source)),
+            [non_exhaustive(&["Circle", "Empty"])]
+        );
+    }
+
+    #[test]
+    fn guarded_arm_does_not_cover_its_variant() {
+        let source = "
+            fn f(shape: Shape) {
+                match shape {
+                    Rect(w, h) if w == h => print w;
+                    Circle(_) => print 1;
+                    Empty => print 0;
+                }
+            }
+        ";
+        assert_eq!(
+            errors(&format!("{}{}", SHAPE, source)),
+            [non_exhaustive(&["Rect"])]
+        );
+    }
+
+    #[test]
+    fn wildcard_covers_the_rest() {
+        let source = "
+            fn f(shape: Shape) {
+                match shape {
+                    Rect(w, h) if w == h => print w;
+                    _ => print 0;
+                }
+            }
+        ";
+        assert_eq!(errors(&format!("{}{}", SHAPE, source)), []);
+    }
+}
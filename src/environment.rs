@@ -0,0 +1,90 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::ast::Type;
+use super::value::Value;
+
+pub type Env = Rc<RefCell<Environment>>;
+
+/// The variables of one scope, linked to the scope that encloses it.
+///
+/// Environments are reference counted so a closure can keep the scope it was
+/// created in alive after the block that created it has finished.
+#[derive(Default)]
+pub struct Environment {
+    values: HashMap<String, Value>,
+    /// The annotated types of the variables that have one.
+    types: HashMap<String, Type>,
+    enclosing: Option<Env>,
+}
+
+impl Environment {
+    pub fn root() -> Env {
+        Rc::new(RefCell::new(Self::default()))
+    }
+
+    pub fn child(enclosing: &Env) -> Env {
+        Rc::new(RefCell::new(Self {
+            values: HashMap::new(),
+            types: HashMap::new(),
+            enclosing: Some(Rc::clone(enclosing)),
+        }))
+    }
+
+    pub fn define(&mut self, name: String, value: Value) {
+        self.types.remove(&name);
+        self.values.insert(name, value);
+    }
+
+    /// Defines a variable that may only be assigned values of type `type_`.
+    pub fn define_typed(&mut self, name: String, value: Value, type_: Type) {
+        self.types.insert(name.clone(), type_);
+        self.values.insert(name, value);
+    }
+
+    pub fn type_of(&self, name: &str) -> Option<&Type> {
+        self.types.get(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.values.get(name).cloned()
+    }
+
+    /// The variables of this scope, sorted by name.
+    pub fn variables(&self) -> Vec<(String, Value)> {
+        let mut variables: Vec<_> = self
+            .values
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        variables.sort_by(|(left, _), (right, _)| left.cmp(right));
+        variables
+    }
+
+    /// Replaces the value of an existing variable in this scope. Returns
+    /// `false` if this scope does not declare it.
+    pub fn assign(&mut self, name: &str, value: Value) -> bool {
+        match self.values.get_mut(name) {
+            Some(saved_value) => {
+                *saved_value = value;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Walks `depth` scopes up from `environment`.
+pub fn ancestor(environment: &Env, depth: usize) -> Env {
+    let mut environment = Rc::clone(environment);
+    for _ in 0..depth {
+        let enclosing = environment
+            .borrow()
+            .enclosing
+            .clone()
+            .expect("the resolver only produces depths of existing scopes");
+        environment = enclosing;
+    }
+    environment
+}
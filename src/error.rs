@@ -0,0 +1,583 @@
+use std::fmt;
+
+use super::span::Span;
+
+/// Errors found while splitting the source into tokens.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexicalError {
+    UnexpectedCharacter(char),
+    UnterminatedString,
+    /// A `}` in a string that does not end an embedded expression.
+    UnmatchedBrace,
+    /// A backslash followed by a char that does not form an escape.
+    InvalidEscape(String),
+    /// A `\u` escape without 1 to 6 hex digits in braces naming a char.
+    InvalidUnicodeEscape(String),
+    MultipleDecimalPoints,
+    /// A char that is not a digit of the base, given as the radix.
+    InvalidDigit(char, u32),
+    /// A `0x`, `0o` or `0b` prefix with no digits after it.
+    MissingDigits(String),
+    /// An `e` with no digits after it.
+    MissingExponent,
+    /// A `_` that is not between two digits.
+    MisplacedSeparator,
+    NumberTooLarge,
+    /// Strings embedded in each other more than `parser::MAX_NESTING` deep.
+    TooDeeplyNested,
+}
+
+/// Errors in the structure of the program, found before it runs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxError {
+    UnexpectedEndOfInput,
+    /// `found` is `None` at the end of the input.
+    Expected {
+        expected: String,
+        found: Option<String>,
+    },
+    InvalidNumber(String),
+    UnknownType(String),
+    MissingTypeOrValue(String),
+    NotAssignable(String),
+    BreakOutsideLoop,
+    ContinueOutsideLoop,
+    ReturnOutsideFunction,
+    AlreadyDeclared(String),
+    DuplicateParameter(String),
+    UndeclaredVariable(String),
+    UndeclaredAssignment(String),
+    /// `for key, value in` over a range.
+    KeyedRange,
+    DuplicateField(String),
+    DuplicateVariant(String),
+    /// A global variant name already used by another enum.
+    VariantAlreadyDeclared {
+        variant: String,
+        enum_: String,
+    },
+    /// A name bound twice by one pattern.
+    DuplicateBinding(String),
+    /// Nesting deeper than `parser::MAX_NESTING`.
+    TooDeeplyNested,
+}
+
+/// Values of the wrong type. Type names are spelled as in `Value::type_name`
+/// and `Type`'s `Display`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeError {
+    Mismatch {
+        expected: String,
+        found: String,
+    },
+    ArgumentMismatch {
+        function: String,
+        param: String,
+        expected: String,
+        found: String,
+    },
+    /// `found` is `None` when the function returned nothing.
+    ReturnMismatch {
+        function: String,
+        expected: String,
+        found: Option<String>,
+    },
+    InvalidOperand {
+        op: String,
+        operand: String,
+    },
+    InvalidOperands {
+        op: String,
+        left: String,
+        right: String,
+    },
+    ArityMismatch {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+    NotCallable(String),
+    NotIterable(String),
+    NotIndexable(String),
+    NotSliceable(String),
+    /// A value that cannot be a map key, which must be a number, string or
+    /// bool.
+    InvalidKey(String),
+    /// Building a struct from a name that is not one.
+    NotAStruct(String),
+    UnknownField {
+        type_: String,
+        field: String,
+    },
+    UnknownMember {
+        module: String,
+        member: String,
+    },
+    MissingField {
+        struct_: String,
+        field: String,
+    },
+    FieldMismatch {
+        struct_: String,
+        field: String,
+        expected: String,
+        found: String,
+    },
+    NoDefault {
+        type_: String,
+        name: String,
+    },
+    /// Matching on a value that is not of an enum type.
+    NotAnEnum(String),
+    /// `enum_` is `None` when the variant is not in any enum.
+    UnknownVariant {
+        enum_: Option<String>,
+        variant: String,
+    },
+    /// A pattern that binds a different number of values than its variant
+    /// carries.
+    PatternMismatch {
+        variant: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A `match` without an arm for the variants listed.
+    NonExhaustiveMatch(Vec<String>),
+    /// Using a value of an optional type without checking it for `none`.
+    UncheckedOptional(String),
+}
+
+/// Errors that can only be detected while the program runs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    DivisionByZero,
+    /// An int operation whose result does not fit in 64 bits.
+    IntegerOverflow(String),
+    /// A value that cannot be converted to the type named.
+    InvalidConversion {
+        value: String,
+        type_: String,
+    },
+    ZeroStep,
+    UndefinedVariable(String),
+    IndexOutOfBounds {
+        index: i64,
+        length: usize,
+    },
+    /// A slice whose start comes after its end, after counting negative
+    /// bounds from the end.
+    InvalidSlice {
+        start: usize,
+        end: usize,
+    },
+    PopFromEmptyList,
+    MissingKey(String),
+    /// Splitting a string at `""`.
+    EmptySeparator,
+    /// A math function called with arguments it is not defined for, such as
+    /// the square root of a negative number.
+    Domain {
+        function: String,
+        args: String,
+    },
+    /// A math function whose result is too large for a float.
+    OutOfRange(String),
+    /// Clamping to a range whose lower bound is above its upper bound.
+    InvalidBounds {
+        low: String,
+        high: String,
+    },
+    /// Repeating a string a negative number of times, or too many.
+    InvalidRepeat(i64),
+    /// A call nested deeper than `interpreter::MAX_CALL_DEPTH`, usually from
+    /// recursion that does not stop.
+    StackOverflow {
+        function: String,
+    },
+    /// A `match` none of whose arms applies to the value.
+    NoMatch(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    Lexical(LexicalError),
+    Syntax(SyntaxError),
+    Type(TypeError),
+    Runtime(RuntimeError),
+}
+
+/// Any error produced while tokenizing, parsing or running a program,
+/// together with the source it is about.
+///
+/// Boxed so results that can fail stay small.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunError(Box<Inner>);
+
+#[derive(Debug, Clone, PartialEq)]
+struct Inner {
+    kind: ErrorKind,
+    span: Span,
+}
+
+impl FunError {
+    pub fn new(kind: impl Into<ErrorKind>, span: &Span) -> Self {
+        Self(Box::new(Inner {
+            kind: kind.into(),
+            span: span.clone(),
+        }))
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.0.kind
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.0.span
+    }
+
+    /// Renders the error in the style of
+    ///
+    /// ```text
+    /// syntax error: Expected semicolon, got "print"
+    ///  --> foo.fun:2:1
+    ///   |
+    /// 2 | print x;
+    ///   | ^^^^^
+    /// ```
+    ///
+    /// `source` is the text of the file the error points into.
+    pub fn render(&self, source: &str) -> String {
+        let span = self.span();
+        let line_start = source[..span.start.min(source.len())]
+            .rfind('\n')
+            .map_or(0, |index| index + 1);
+        let line_end = source[line_start..]
+            .find('\n')
+            .map_or(source.len(), |index| line_start + index);
+        let line = &source[line_start..line_end];
+
+        let underline_start = span.start.clamp(line_start, line_end);
+        let underline_end = span.end.clamp(underline_start, line_end);
+        let padding = source[line_start..underline_start].chars().count();
+        let width = source[underline_start..underline_end]
+            .chars()
+            .count()
+            .max(1);
+
+        let number = span.line.to_string();
+        let gutter = " ".repeat(number.len());
+        format!(
+            "{} error: {}\n{}--> {}\n{} |\n{} | {}\n{} | {}{}",
+            self.kind().category(),
+            self.kind(),
+            gutter,
+            span,
+            gutter,
+            number,
+            line,
+            gutter,
+            " ".repeat(padding),
+            "^".repeat(width)
+        )
+    }
+}
+
+impl ErrorKind {
+    pub fn category(&self) -> &'static str {
+        match self {
+            ErrorKind::Lexical(_) => "lexical",
+            ErrorKind::Syntax(_) => "syntax",
+            ErrorKind::Type(_) => "type",
+            ErrorKind::Runtime(_) => "runtime",
+        }
+    }
+}
+
+impl From<LexicalError> for ErrorKind {
+    fn from(error: LexicalError) -> Self {
+        ErrorKind::Lexical(error)
+    }
+}
+
+impl From<SyntaxError> for ErrorKind {
+    fn from(error: SyntaxError) -> Self {
+        ErrorKind::Syntax(error)
+    }
+}
+
+impl From<TypeError> for ErrorKind {
+    fn from(error: TypeError) -> Self {
+        ErrorKind::Type(error)
+    }
+}
+
+impl From<RuntimeError> for ErrorKind {
+    fn from(error: RuntimeError) -> Self {
+        ErrorKind::Runtime(error)
+    }
+}
+
+impl fmt::Display for LexicalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LexicalError::UnexpectedCharacter(char) => {
+                write!(f, "Unexpected character: {:?}", char)
+            }
+            LexicalError::UnterminatedString => write!(f, "Unterminated string literal"),
+            LexicalError::InvalidEscape(escape) => write!(f, "Invalid escape '{}'", escape),
+            LexicalError::InvalidUnicodeEscape(escape) => write!(
+                f,
+                "Invalid unicode escape '{}', expected the hex code of a char in braces",
+                escape
+            ),
+            LexicalError::UnmatchedBrace => {
+                write!(f, "Unmatched '}}' in string, write '}}}}' for a brace")
+            }
+            LexicalError::MultipleDecimalPoints => {
+                write!(f, "Multiple decimal points in number")
+            }
+            LexicalError::InvalidDigit(char, radix) => {
+                let base = match radix {
+                    2 => "binary",
+                    8 => "octal",
+                    16 => "hexadecimal",
+                    _ => "decimal",
+                };
+                write!(f, "Invalid digit {:?} in {} number", char, base)
+            }
+            LexicalError::MissingDigits(prefix) => {
+                write!(f, "Expected digits after '{}'", prefix)
+            }
+            LexicalError::MissingExponent => write!(f, "Expected digits in the exponent"),
+            LexicalError::MisplacedSeparator => {
+                write!(f, "Digit separator '_' must be between digits")
+            }
+            LexicalError::NumberTooLarge => write!(f, "Number is too large"),
+            LexicalError::TooDeeplyNested => write!(
+                f,
+                "Strings are embedded more than {} deep",
+                crate::parser::MAX_NESTING
+            ),
+        }
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyntaxError::UnexpectedEndOfInput => write!(f, "Unexpected end of input"),
+            SyntaxError::Expected {
+                expected,
+                found: Some(found),
+            } => write!(f, "Expected {}, got {:?}", expected, found),
+            SyntaxError::Expected {
+                expected,
+                found: None,
+            } => write!(f, "Expected {}, got end of input", expected),
+            SyntaxError::InvalidNumber(number) => write!(f, "Invalid number {:?}", number),
+            SyntaxError::UnknownType(type_) => write!(f, "Unknown type {:?}", type_),
+            SyntaxError::MissingTypeOrValue(name) => {
+                write!(f, "Expected type annotation or value for {:?}", name)
+            }
+            SyntaxError::NotAssignable(name) => write!(f, "{:?} is not assignable", name),
+            SyntaxError::BreakOutsideLoop => write!(f, "'break' outside of a loop"),
+            SyntaxError::ContinueOutsideLoop => write!(f, "'continue' outside of a loop"),
+            SyntaxError::ReturnOutsideFunction => write!(f, "'return' outside of a function"),
+            SyntaxError::AlreadyDeclared(name) => {
+                write!(f, "{:?} is already declared in this scope", name)
+            }
+            SyntaxError::DuplicateParameter(name) => write!(f, "Duplicate parameter {:?}", name),
+            SyntaxError::UndeclaredVariable(name) => {
+                write!(f, "Cannot read undeclared variable {:?}", name)
+            }
+            SyntaxError::UndeclaredAssignment(name) => {
+                write!(f, "Cannot assign to undeclared variable {:?}", name)
+            }
+            SyntaxError::KeyedRange => write!(f, "A range only has one loop variable"),
+            SyntaxError::DuplicateField(name) => write!(f, "Duplicate field {:?}", name),
+            SyntaxError::DuplicateVariant(name) => write!(f, "Duplicate variant {:?}", name),
+            SyntaxError::VariantAlreadyDeclared { variant, enum_ } => {
+                write!(f, "Variant {:?} is already declared by {}", variant, enum_)
+            }
+            SyntaxError::DuplicateBinding(name) => {
+                write!(f, "{:?} is bound more than once in this pattern", name)
+            }
+            SyntaxError::TooDeeplyNested => {
+                write!(f, "Nested more than {} deep", crate::parser::MAX_NESTING)
+            }
+        }
+    }
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeError::Mismatch { expected, found } => {
+                write!(f, "Expected {}, got {}", expected, found)
+            }
+            TypeError::ArgumentMismatch {
+                function,
+                param,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Expected {} for parameter {:?} of {:?}, got {}",
+                expected, param, function, found
+            ),
+            TypeError::ReturnMismatch {
+                function,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Function {:?} should return {}, got {}",
+                function,
+                expected,
+                found.as_deref().unwrap_or("nothing")
+            ),
+            TypeError::InvalidOperand { op, operand } => {
+                write!(f, "Cannot apply {} to {}", op, operand)
+            }
+            TypeError::InvalidOperands { op, left, right } => {
+                write!(f, "Cannot apply {} to {} and {}", op, left, right)
+            }
+            TypeError::ArityMismatch {
+                function,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Function {:?} expects {} arguments, got {}",
+                function, expected, found
+            ),
+            TypeError::NotCallable(type_) => write!(f, "{} is not callable", type_),
+            TypeError::NotIterable(type_) => write!(f, "Cannot iterate over {}", type_),
+            TypeError::NotIndexable(type_) => write!(f, "Cannot index {}", type_),
+            TypeError::NotSliceable(type_) => write!(f, "Cannot slice {}", type_),
+            TypeError::InvalidKey(type_) => write!(f, "{} cannot be used as a map key", type_),
+            TypeError::NotAStruct(name) => write!(f, "{:?} is not a struct", name),
+            TypeError::UnknownField { type_, field } => {
+                write!(f, "{} has no field {:?}", type_, field)
+            }
+            TypeError::UnknownMember { module, member } => {
+                write!(f, "Module {} has no member {:?}", module, member)
+            }
+            TypeError::MissingField { struct_, field } => {
+                write!(f, "Missing field {:?} of {}", field, struct_)
+            }
+            TypeError::FieldMismatch {
+                struct_,
+                field,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Expected {} for field {:?} of {}, got {}",
+                expected, field, struct_, found
+            ),
+            TypeError::NoDefault { type_, name } => write!(
+                f,
+                "{} has no default value, {:?} must be initialized",
+                type_, name
+            ),
+            TypeError::NotAnEnum(type_) => write!(f, "Cannot match on {}", type_),
+            TypeError::UnknownVariant {
+                enum_: Some(enum_),
+                variant,
+            } => write!(f, "{} has no variant {:?}", enum_, variant),
+            TypeError::UnknownVariant {
+                enum_: None,
+                variant,
+            } => write!(f, "No enum has a variant {:?}", variant),
+            TypeError::PatternMismatch {
+                variant,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Variant {:?} carries {} values, the pattern has {}",
+                variant, expected, found
+            ),
+            TypeError::NonExhaustiveMatch(variants) => {
+                write!(f, "Match does not cover {}", variants.join(", "))
+            }
+            TypeError::UncheckedOptional(type_) => write!(
+                f,
+                "{} may be none, compare it with none before using it",
+                type_
+            ),
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeError::DivisionByZero => write!(f, "Division by zero"),
+            RuntimeError::IntegerOverflow(op) => write!(f, "Integer overflow in '{}'", op),
+            RuntimeError::InvalidConversion { value, type_ } => {
+                write!(f, "Cannot convert {} to {}", value, type_)
+            }
+            RuntimeError::ZeroStep => write!(f, "Range step cannot be zero"),
+            RuntimeError::UndefinedVariable(name) => {
+                write!(f, "Unknown identifier {:?}", name)
+            }
+            RuntimeError::IndexOutOfBounds { index, length } => {
+                write!(f, "Index {} is out of bounds for length {}", index, length)
+            }
+            RuntimeError::InvalidSlice { start, end } => {
+                write!(f, "Slice starts at {} but ends at {}", start, end)
+            }
+            RuntimeError::PopFromEmptyList => write!(f, "Cannot pop from an empty list"),
+            RuntimeError::MissingKey(key) => write!(f, "Key {} is not in the map", key),
+            RuntimeError::EmptySeparator => write!(f, "Cannot split at an empty separator"),
+            RuntimeError::Domain { function, args } => {
+                write!(f, "{} is not defined for {}", function, args)
+            }
+            RuntimeError::OutOfRange(function) => {
+                write!(f, "The result of {} is too large", function)
+            }
+            RuntimeError::InvalidBounds { low, high } => write!(
+                f,
+                "Lower bound {} is greater than upper bound {}",
+                low, high
+            ),
+            RuntimeError::InvalidRepeat(times) => {
+                write!(f, "Cannot repeat a string {} times", times)
+            }
+            RuntimeError::StackOverflow { function } => write!(
+                f,
+                "Stack overflow calling {:?}, calls nest more than {} deep",
+                function,
+                crate::interpreter::MAX_CALL_DEPTH
+            ),
+            RuntimeError::NoMatch(value) => write!(f, "No arm of the match applies to {}", value),
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::Lexical(error) => write!(f, "{}", error),
+            ErrorKind::Syntax(error) => write!(f, "{}", error),
+            ErrorKind::Type(error) => write!(f, "{}", error),
+            ErrorKind::Runtime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl fmt::Display for FunError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} error: {}",
+            self.span(),
+            self.kind().category(),
+            self.kind()
+        )
+    }
+}
+
+impl std::error::Error for FunError {}
@@ -1,345 +1,1210 @@
-use std::collections::HashMap;
-use std::io::{Error, ErrorKind};
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::iter;
+use std::rc::Rc;
+use std::thread;
 
-use super::tokenizer::*;
+use super::ast::*;
+use super::builtins::{self, BUILTINS, MODULES};
+use super::environment::{ancestor, Env, Environment};
+use super::error::{FunError, RuntimeError, SyntaxError, TypeError};
+use super::map::Map;
+use super::span::Span;
+use super::value::{Closure, Instance, Value, VariantValue};
 
-macro_rules! check_for_semicolon {
-    ($interpreter:ident, $peek_depth:literal) => {
-        let next_token = $interpreter.peek($peek_depth + 1).unwrap();
-        match next_token.token_type {
-            TokenType::Semicolon => {}
-            _ => {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    format!("Expected semicolon, got {:?}", next_token),
-                ));
-            }
-        }
-    };
+/// How deeply calls can nest before the program stops with
+/// `RuntimeError::StackOverflow`.
+pub const MAX_CALL_DEPTH: usize = 1000;
+
+/// The stack size `with_stack` gives, enough for `MAX_CALL_DEPTH` nested calls
+/// of functions whose bodies nest statements several levels deep, even in
+/// debug builds.
+const STACK_SIZE: usize = 512 * 1024 * 1024;
+
+/// Runs `f` on a thread with a stack large enough to reach `MAX_CALL_DEPTH`,
+/// which the stack of the main thread is not, and returns its result.
+pub fn with_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    thread::scope(|scope| {
+        let thread = thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, f)
+            .expect("failed to spawn the interpreter thread");
+        thread
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
 }
 
-#[derive(Debug, Clone, PartialEq)]
-struct IdentValue {
-    value: String,
-    type_: LiteralType,
+/// How control leaves a statement.
+enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(Value),
 }
 
 pub struct Interpreter {
-    tokens: Vec<Token>,
-    index: usize,
-    mem: HashMap<String, IdentValue>,
+    globals: Env,
+    /// The scope of the statement being executed.
+    environment: Env,
+    /// How many calls are running.
+    call_depth: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
-    pub fn new(tokens: Vec<Token>) -> Self {
+    pub fn new() -> Self {
+        let globals = Environment::root();
+        for builtin in BUILTINS {
+            globals
+                .borrow_mut()
+                .define(builtin.name.to_string(), Value::Builtin(builtin));
+        }
+        for module in MODULES {
+            globals
+                .borrow_mut()
+                .define(module.name.to_string(), Value::Module(module));
+        }
         Self {
-            tokens,
-            index: 0,
-            mem: HashMap::new(),
+            environment: Rc::clone(&globals),
+            globals,
+            call_depth: 0,
+        }
+    }
+
+    /// The scope a resolved variable lives in.
+    fn scope_of(&self, depth: Option<usize>) -> Env {
+        match depth {
+            Some(depth) => ancestor(&self.environment, depth),
+            None => Rc::clone(&self.globals),
         }
     }
 
-    fn peek(&self, offset: usize) -> Option<&Token> {
-        if self.index + offset >= self.tokens.len() {
-            None
-        } else {
-            Some(&self.tokens[self.index + offset])
+    /// Runs `statements` in `environment`, restoring the current environment
+    /// afterwards even if one of them fails.
+    fn execute_in(&mut self, statements: &[Stmt], environment: Env) -> Result<Flow, FunError> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let result = self.execute_all(statements);
+        self.environment = previous;
+        result
+    }
+
+    fn execute_block(&mut self, statements: &[Stmt]) -> Result<Flow, FunError> {
+        let environment = Environment::child(&self.environment);
+        self.execute_in(statements, environment)
+    }
+
+    /// Runs one iteration of a `for` loop body with the loop variables bound
+    /// in a fresh scope.
+    fn execute_iteration(
+        &mut self,
+        variables: Vec<(&str, Value)>,
+        body: &[Stmt],
+    ) -> Result<Flow, FunError> {
+        let environment = Environment::child(&self.environment);
+        for (variable, value) in variables {
+            environment.borrow_mut().define(variable.to_string(), value);
         }
+        self.execute_in(body, environment)
     }
 
-    fn consume(&mut self) -> std::io::Result<&Token> {
-        let token = &self.tokens[self.index];
-        self.index += 1;
-        Ok(token)
+    /// Evaluates `expr` in `environment`, restoring the current environment
+    /// afterwards.
+    fn evaluate_in(&mut self, expr: &Expr, environment: Env) -> Result<Value, FunError> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let result = self.evaluate(expr);
+        self.environment = previous;
+        result
     }
 
-    fn consume_times(&mut self, times: usize) -> std::io::Result<()> {
-        for _ in 0..times {
-            self.consume()?;
+    fn execute_all(&mut self, statements: &[Stmt]) -> Result<Flow, FunError> {
+        for statement in statements {
+            match self.execute(statement)? {
+                Flow::Normal => {}
+                flow => return Ok(flow),
+            }
         }
 
+        Ok(Flow::Normal)
+    }
+
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<(), FunError> {
+        self.execute_all(statements)?;
         Ok(())
     }
 
-    pub fn interpret(&mut self) -> std::io::Result<()> {
-        while self.peek(0).is_some() {
-            let token = self.peek(0).unwrap();
-            match token.token_type {
-                TokenType::Keyword(KeywordType::Let) => {
-                    let ident_token = self.peek(1).unwrap();
-                    match ident_token.token_type {
-                        TokenType::Ident => {
-                            let next_token = self.peek(2).unwrap();
-                            match next_token.token_type {
-                                TokenType::Assignment => {
-                                    let ident_value_token = self.peek(3).unwrap();
-                                    let TokenType::Literal(type_) = &ident_value_token.token_type
-                                    else {
-                                        return Err(Error::new(
-                                            ErrorKind::Other,
-                                            format!(
-                                                "Expected literal type, got {:?}",
-                                                ident_value_token
-                                            ),
-                                        ));
-                                    };
-
-                                    self.mem.insert(
-                                        ident_token.value.clone(),
-                                        IdentValue {
-                                            value: ident_value_token.value.clone(),
-                                            type_: type_.clone(),
-                                        },
-                                    );
-
-                                    check_for_semicolon!(self, 3);
-
-                                    self.consume_times(4)?;
-                                }
-                                TokenType::Colon => {
-                                    let type_token = self.peek(3).unwrap();
-                                    let next_token = self.peek(4).unwrap();
-                                    match next_token.token_type {
-                                        TokenType::Assignment => {
-                                            let ident_value_token = self.peek(5).unwrap();
-                                            let TokenType::Literal(type_) =
-                                                &ident_value_token.token_type
-                                            else {
-                                                return Err(Error::new(
-                                                    ErrorKind::Other,
-                                                    format!(
-                                                        "Expected literal type, got {:?}",
-                                                        ident_value_token
-                                                    ),
-                                                ));
-                                            };
-
-                                            match type_token.value.as_str() {
-                                                "string" => {
-                                                    if type_.to_owned() != LiteralType::String {
-                                                        return Err(Error::new(
-                                                            ErrorKind::Other,
-                                                            format!(
-                                                                "Expected String, got {:?}: {:?}",
-                                                                type_, ident_value_token.value
-                                                            ),
-                                                        ));
-                                                    }
-
-                                                    self.mem.insert(
-                                                        ident_token.value.clone(),
-                                                        IdentValue {
-                                                            value: ident_value_token.value.clone(),
-                                                            type_: type_.clone(),
-                                                        },
-                                                    );
-                                                }
-                                                "number" => {
-                                                    if type_.to_owned() != LiteralType::Number {
-                                                        return Err(Error::new(
-                                                            ErrorKind::Other,
-                                                            format!(
-                                                                "Expected Number, got {:?}: {:?}",
-                                                                type_, ident_value_token.value
-                                                            ),
-                                                        ));
-                                                    }
-
-                                                    self.mem.insert(
-                                                        ident_token.value.clone(),
-                                                        IdentValue {
-                                                            value: ident_value_token.value.clone(),
-                                                            type_: type_.clone(),
-                                                        },
-                                                    );
-                                                }
-                                                _ => {
-                                                    return Err(Error::new(
-                                                        ErrorKind::Other,
-                                                        format!("Unknown type {:?}", type_token),
-                                                    ));
-                                                }
-                                            }
-
-                                            check_for_semicolon!(self, 5);
-
-                                            self.consume_times(2)?;
-                                        }
-                                        _ => match type_token.value.as_str() {
-                                            "string" => {
-                                                self.mem.insert(
-                                                    ident_token.value.clone(),
-                                                    IdentValue {
-                                                        value: String::new(),
-                                                        type_: LiteralType::String,
-                                                    },
-                                                );
-                                            }
-                                            "number" => {
-                                                self.mem.insert(
-                                                    ident_token.value.clone(),
-                                                    IdentValue {
-                                                        value: String::from("0"),
-                                                        type_: LiteralType::Number,
-                                                    },
-                                                );
-                                            }
-                                            _ => {
-                                                return Err(Error::new(
-                                                    ErrorKind::Other,
-                                                    format!("Unknown type {:?}", type_token),
-                                                ));
-                                            }
-                                        },
-                                    }
-
-                                    self.consume_times(4)?;
-                                }
-                                _ => {}
-                            };
+    /// Runs a top-level statement. For an expression statement, returns its
+    /// value.
+    pub fn interpret_statement(&mut self, statement: &Stmt) -> Result<Option<Value>, FunError> {
+        match &statement.kind {
+            StmtKind::Expression(expr) => Ok(Some(self.evaluate(expr)?)),
+            _ => {
+                self.execute(statement)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// The global variables and their values, sorted by name, leaving out
+    /// the builtins and modules.
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        let mut variables = self.globals.borrow().variables();
+        variables.retain(|(_, value)| !matches!(value, Value::Builtin(_) | Value::Module(_)));
+        variables
+    }
+
+    fn execute(&mut self, statement: &Stmt) -> Result<Flow, FunError> {
+        let span = &statement.span;
+        match &statement.kind {
+            StmtKind::Let { name, type_, value } => {
+                let value = match (type_, value) {
+                    (Some(type_), Some(value)) => {
+                        let value = self.evaluate(value)?;
+                        if !value.is_type(type_) {
+                            return Err(mismatch(type_, value.type_name(), span));
                         }
-                        _ => {}
+                        value
                     }
-                }
-                TokenType::Ident => {
-                    if token.value == "print" {
-                        let next_token = self.peek(1).unwrap();
-                        match next_token.token_type {
-                            TokenType::OpenParen => {
-                                let next_token = self.peek(2).unwrap();
-                                match next_token.token_type {
-                                    TokenType::Ident => {
-                                        let Some(ident_value) = self.mem.get(&next_token.value)
-                                        else {
-                                            return Err(Error::new(
-                                                ErrorKind::Other,
-                                                format!(
-                                                    "Unknown identifier {:?}",
-                                                    next_token.value
-                                                ),
-                                            ));
-                                        };
-                                        match ident_value.type_ {
-                                            LiteralType::String => {
-                                                println!("{}", ident_value.value);
-                                            }
-                                            LiteralType::Number => {
-                                                println!(
-                                                    "{}",
-                                                    ident_value
-                                                        .value
-                                                        .parse::<f64>()
-                                                        .to_owned()
-                                                        .unwrap()
-                                                );
-                                            }
-                                        }
-                                    }
-                                    TokenType::Literal(LiteralType::String) => {
-                                        println!("{}", next_token.value);
-                                    }
-                                    TokenType::Literal(LiteralType::Number) => {
-                                        println!(
-                                            "{}",
-                                            next_token.value.parse::<f64>().to_owned().unwrap()
-                                        );
-                                    }
-                                    _ => {
-                                        continue;
-                                    }
-                                }
-                                self.consume()?;
-                            }
-                            TokenType::Literal(LiteralType::String) => {
-                                println!("{}", next_token.value);
-                            }
-                            TokenType::Literal(LiteralType::Number) => {
-                                println!("{}", next_token.value);
-                            }
-                            TokenType::Assignment => {
-                                return Err(Error::new(
-                                    ErrorKind::Other,
-                                    "'print' is not assignable",
-                                ));
-                            }
-                            _ => {}
+                    (None, Some(value)) => self.evaluate(value)?,
+                    (Some(type_), None) => match Value::default_for(type_) {
+                        Some(value) => value,
+                        None => {
+                            return Err(FunError::new(
+                                TypeError::NoDefault {
+                                    type_: type_.to_string(),
+                                    name: name.clone(),
+                                },
+                                span,
+                            ));
                         }
-                        let next_token = self.peek(2).unwrap();
-                        match next_token.token_type {
-                            TokenType::CloseParen => {
-                                check_for_semicolon!(self, 2);
-                            }
-                            _ => {
-                                return Err(Error::new(
-                                    ErrorKind::Other,
-                                    format!("Expected close paren, got {:?}", next_token),
-                                ));
-                            }
-                        };
-                        self.consume_times(3)?;
+                    },
+                    (None, None) => {
+                        return Err(FunError::new(
+                            SyntaxError::MissingTypeOrValue(name.clone()),
+                            span,
+                        ));
+                    }
+                };
+
+                let mut environment = self.environment.borrow_mut();
+                match type_ {
+                    Some(type_) => environment.define_typed(name.clone(), value, type_.clone()),
+                    None => environment.define(name.clone(), value),
+                }
+            }
+            StmtKind::Assign { name, value, depth } => {
+                let value = self.evaluate(value)?;
+
+                let scope = self.scope_of(*depth);
+                let Some(saved_value) = scope.borrow().get(name) else {
+                    return Err(FunError::new(
+                        SyntaxError::UndeclaredAssignment(name.clone()),
+                        span,
+                    ));
+                };
+
+                // Annotated variables take any value of their type, others
+                // keep the type of their first value
+                match scope.borrow().type_of(name) {
+                    Some(type_) if !value.is_type(type_) => {
+                        return Err(mismatch(type_, value.type_name(), span));
+                    }
+                    None if value.type_name() != saved_value.type_name() => {
+                        return Err(mismatch(saved_value.type_name(), value.type_name(), span));
+                    }
+                    _ => {}
+                }
+
+                scope.borrow_mut().assign(name, value);
+            }
+            StmtKind::SetIndex {
+                target,
+                index,
+                value,
+            } => {
+                let target_value = self.evaluate(target)?;
+                let index_value = self.evaluate(index)?;
+                let value = self.evaluate(value)?;
+                match target_value {
+                    Value::List(list) => {
+                        let int = index_int(index_value, &index.span)?;
+                        let length = list.borrow().len();
+                        let position = builtins::position(int, length, false, &index.span)?;
+                        list.borrow_mut()[position] = value;
+                    }
+                    Value::Map(map) => {
+                        builtins::check_key(&index_value, &index.span)?;
+                        map.borrow_mut().insert(index_value, value);
+                    }
+                    target_value => return Err(not_indexable(&target_value, &target.span)),
+                }
+            }
+            StmtKind::SetField {
+                target,
+                name,
+                value,
+            } => {
+                let instance = self.evaluate_instance(target, name)?;
+                let value = self.evaluate(value)?;
+                let struct_ = Rc::clone(&instance.borrow().struct_);
+                let (index, field) = struct_
+                    .field(name)
+                    .ok_or_else(|| unknown_field(&struct_.name, name, span))?;
+                // Checked before borrowing the instance mutably, since the
+                // value may be the instance itself
+                if !value.is_type(&field.type_) {
+                    return Err(field_mismatch(&struct_.name, field, &value, span));
+                }
+                instance.borrow_mut().fields[index] = value;
+            }
+            StmtKind::Print(value) => {
+                println!("{}", self.evaluate(value)?);
+            }
+            StmtKind::Expression(expr) => {
+                self.evaluate(expr)?;
+            }
+            StmtKind::Function(function) => {
+                let closure = self.closure(function);
+                let name = function.name.clone().expect("declared functions are named");
+                self.environment.borrow_mut().define(name, closure);
+            }
+            StmtKind::Struct(struct_) => {
+                self.environment
+                    .borrow_mut()
+                    .define(struct_.name.clone(), Value::Struct(Rc::clone(struct_)));
+            }
+            StmtKind::Enum(enum_) => {
+                let mut environment = self.environment.borrow_mut();
+                environment.define(enum_.name.clone(), Value::Enum(Rc::clone(enum_)));
+                for (index, variant) in enum_.variants.iter().enumerate() {
+                    // A variant without values is a value itself
+                    let value = if variant.types.is_empty() {
+                        Value::Variant(Rc::new(VariantValue {
+                            enum_: Rc::clone(enum_),
+                            index,
+                            values: Vec::new(),
+                        }))
                     } else {
-                        let next_token = self.peek(1).unwrap();
-                        match next_token.token_type {
-                            TokenType::Assignment => {
-                                let ident_value_token = self.peek(2).unwrap();
-                                let TokenType::Literal(type_) = &ident_value_token.token_type
-                                else {
-                                    return Err(Error::new(
-                                        ErrorKind::Other,
-                                        format!(
-                                            "Expected literal type, got {:?}",
-                                            ident_value_token
-                                        ),
-                                    ));
-                                };
-
-                                let Some(saved_value) = self.mem.get(&token.value) else {
-                                    return Err(Error::new(
-                                        ErrorKind::Other,
-                                        format!("Unknown identifier {:?}", token.value),
-                                    ));
-                                };
-
-                                if type_.to_owned() != saved_value.type_ {
-                                    return Err(Error::new(
-                                        ErrorKind::Other,
-                                        format!(
-                                            "Expected {:?}, got {:?}",
-                                            type_, saved_value.type_
-                                        ),
-                                    ));
-                                }
-
-                                self.mem.insert(
-                                    token.value.clone(),
-                                    IdentValue {
-                                        value: ident_value_token.value.clone(),
-                                        type_: type_.clone(),
-                                    },
-                                );
-                                check_for_semicolon!(self, 2);
-
-                                self.consume_times(3)?;
-                            }
-                            TokenType::OpenParen => todo!(),
-                            _ => {
-                                return Err(Error::new(
-                                    ErrorKind::Other,
-                                    format!("Unhandled token: {:?}", next_token),
-                                ));
+                        Value::Constructor(Rc::clone(enum_), index)
+                    };
+                    environment.define(variant.name.clone(), value);
+                }
+            }
+            StmtKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.evaluate(value)?,
+                    None => Value::None,
+                };
+                return Ok(Flow::Return(value));
+            }
+            StmtKind::Block(statements) => {
+                return self.execute_block(statements);
+            }
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if self.evaluate_condition(condition)? {
+                    return self.execute_block(then_branch);
+                } else if let Some(else_branch) = else_branch {
+                    return self.execute(else_branch);
+                }
+            }
+            StmtKind::While { condition, body } => {
+                while self.evaluate_condition(condition)? {
+                    match self.execute_block(body)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Normal | Flow::Continue => {}
+                    }
+                }
+            }
+            StmtKind::For {
+                variable,
+                key: _,
+                iterable:
+                    Iterable::Range {
+                        start,
+                        end,
+                        inclusive,
+                        step,
+                    },
+                body,
+            } => {
+                let start = self.evaluate_number(start)?;
+                let end = self.evaluate_number(end)?;
+                let step = match step {
+                    Some(step) => self.evaluate_number(step)?,
+                    None => Value::Int(1),
+                };
+                if step.as_float() == Some(0.0) {
+                    return Err(FunError::new(RuntimeError::ZeroStep, span));
+                }
+
+                for current in range(start, end, step, *inclusive) {
+                    match self.execute_iteration(vec![(variable, current)], body)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Normal | Flow::Continue => {}
+                    }
+                }
+            }
+            StmtKind::For {
+                key: key_variable,
+                variable,
+                iterable: Iterable::Expr(iterable),
+                body,
+            } => {
+                // Keys and elements are copied first, so changing a list or
+                // map in the body does not affect the loop
+                let entries: Vec<(Value, Value)> = match self.evaluate(iterable)? {
+                    Value::String(string) => string
+                        .chars()
+                        .enumerate()
+                        .map(|(i, char)| (Value::Int(i as i64), Value::String(char.to_string())))
+                        .collect(),
+                    Value::List(values) => values
+                        .borrow()
+                        .iter()
+                        .enumerate()
+                        .map(|(i, value)| (Value::Int(i as i64), value.clone()))
+                        .collect(),
+                    Value::Map(map) if key_variable.is_none() => map
+                        .borrow()
+                        .entries()
+                        .iter()
+                        .map(|(key, _)| (Value::None, key.clone()))
+                        .collect(),
+                    Value::Map(map) => map.borrow().entries().to_vec(),
+                    value => {
+                        return Err(FunError::new(
+                            TypeError::NotIterable(value.type_name().to_string()),
+                            &iterable.span,
+                        ));
+                    }
+                };
+
+                for (key, value) in entries {
+                    let mut variables = vec![(variable.as_str(), value)];
+                    if let Some(key_variable) = key_variable {
+                        variables.insert(0, (key_variable, key));
+                    }
+                    match self.execute_iteration(variables, body)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Normal | Flow::Continue => {}
+                    }
+                }
+            }
+            StmtKind::Match { value, arms } => {
+                let variant = match self.evaluate(value)? {
+                    Value::Variant(variant) => variant,
+                    found => {
+                        return Err(FunError::new(
+                            TypeError::NotAnEnum(found.type_name().to_string()),
+                            &value.span,
+                        ));
+                    }
+                };
+                for arm in arms {
+                    let environment = Environment::child(&self.environment);
+                    if let Pattern::Variant { name, bindings } = &arm.pattern {
+                        let Some((index, declared)) = variant.enum_.variant(name) else {
+                            return Err(FunError::new(
+                                TypeError::UnknownVariant {
+                                    enum_: Some(variant.enum_.name.clone()),
+                                    variant: name.clone(),
+                                },
+                                &arm.span,
+                            ));
+                        };
+                        if index != variant.index {
+                            continue;
+                        }
+                        if bindings.len() != declared.types.len() {
+                            return Err(FunError::new(
+                                TypeError::PatternMismatch {
+                                    variant: name.clone(),
+                                    expected: declared.types.len(),
+                                    found: bindings.len(),
+                                },
+                                &arm.span,
+                            ));
+                        }
+                        for (binding, value) in bindings.iter().zip(&variant.values) {
+                            if let Some(binding) = binding {
+                                environment
+                                    .borrow_mut()
+                                    .define(binding.clone(), value.clone());
                             }
                         }
                     }
+                    if let Some(guard) = &arm.guard {
+                        match self.evaluate_in(guard, Rc::clone(&environment))? {
+                            Value::Bool(true) => {}
+                            Value::Bool(false) => continue,
+                            found => return Err(mismatch("Bool", found.type_name(), &guard.span)),
+                        }
+                    }
+                    return self.execute_in(&arm.body, environment);
+                }
+                return Err(FunError::new(
+                    RuntimeError::NoMatch(Value::Variant(variant).repr()),
+                    &value.span,
+                ));
+            }
+            StmtKind::Break => return Ok(Flow::Break),
+            StmtKind::Continue => return Ok(Flow::Continue),
+        }
+
+        Ok(Flow::Normal)
+    }
+
+    /// Evaluates an expression that must give an int or a float.
+    fn evaluate_number(&mut self, expr: &Expr) -> Result<Value, FunError> {
+        match self.evaluate(expr)? {
+            value @ (Value::Int(_) | Value::Float(_)) => Ok(value),
+            value => Err(mismatch("Number", value.type_name(), &expr.span)),
+        }
+    }
+
+    fn evaluate_index(&mut self, expr: &Expr) -> Result<i64, FunError> {
+        let value = self.evaluate(expr)?;
+        index_int(value, &expr.span)
+    }
+
+    fn evaluate_list(&mut self, expr: &Expr) -> Result<Rc<RefCell<Vec<Value>>>, FunError> {
+        match self.evaluate(expr)? {
+            Value::List(values) => Ok(values),
+            value => Err(FunError::new(
+                TypeError::NotSliceable(value.type_name().to_string()),
+                &expr.span,
+            )),
+        }
+    }
+
+    /// Evaluates the target of a field access of `field`.
+    fn evaluate_instance(
+        &mut self,
+        target: &Expr,
+        field: &str,
+    ) -> Result<Rc<RefCell<Instance>>, FunError> {
+        match self.evaluate(target)? {
+            Value::Instance(instance) => Ok(instance),
+            Value::Module(module) => Err(FunError::new(
+                SyntaxError::NotAssignable(format!("{}.{}", module.name, field)),
+                &target.span,
+            )),
+            value => Err(unknown_field(&value.type_name(), field, &target.span)),
+        }
+    }
+
+    fn evaluate_condition(&mut self, condition: &Expr) -> Result<bool, FunError> {
+        match self.evaluate(condition)? {
+            Value::Bool(bool) => Ok(bool),
+            value => Err(mismatch("Bool", value.type_name(), &condition.span)),
+        }
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> Result<Value, FunError> {
+        let span = &expr.span;
+        match &expr.kind {
+            ExprKind::Int(int) => Ok(Value::Int(*int)),
+            ExprKind::Float(float) => Ok(Value::Float(*float)),
+            ExprKind::String(string) => Ok(Value::String(string.clone())),
+            ExprKind::Interpolation(parts) => {
+                let mut string = String::new();
+                for part in parts {
+                    string.push_str(&self.evaluate(part)?.to_string());
+                }
+                Ok(Value::String(string))
+            }
+            ExprKind::Bool(bool) => Ok(Value::Bool(*bool)),
+            ExprKind::None => Ok(Value::None),
+            ExprKind::Variable { name, depth } => match self.scope_of(*depth).borrow().get(name) {
+                Some(value) => Ok(value.clone()),
+                None => Err(FunError::new(
+                    RuntimeError::UndefinedVariable(name.clone()),
+                    span,
+                )),
+            },
+            ExprKind::Unary { op, expr } => {
+                let value = self.evaluate(expr)?;
+                match (op, value) {
+                    (UnaryOp::Negate, Value::Int(int)) => int
+                        .checked_neg()
+                        .map(Value::Int)
+                        .ok_or_else(|| overflow(op, span)),
+                    (UnaryOp::Negate, Value::Float(float)) => Ok(Value::Float(-float)),
+                    (UnaryOp::Not, Value::Bool(bool)) => Ok(Value::Bool(!bool)),
+                    (op, value) => Err(invalid_operand(op, value.type_name(), span)),
+                }
+            }
+            ExprKind::Binary { left, op, right } => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                binary(left, *op, right, span)
+            }
+            ExprKind::Logical { left, op, right } => {
+                let left = self.evaluate_bool(left, *op)?;
+                let short_circuits = match op {
+                    LogicalOp::And => !left,
+                    LogicalOp::Or => left,
+                };
+                if short_circuits {
+                    return Ok(Value::Bool(left));
+                }
+                Ok(Value::Bool(self.evaluate_bool(right, *op)?))
+            }
+            ExprKind::Call { callee, args } => match self.evaluate(callee)? {
+                Value::Function(closure) => self.call(&closure, args, span),
+                Value::Constructor(enum_, index) => self.construct(enum_, index, args, span),
+                Value::Builtin(builtin) => {
+                    let values = args
+                        .iter()
+                        .map(|arg| self.evaluate(arg))
+                        .collect::<Result<_, _>>()?;
+                    let spans = args.iter().map(|arg| &arg.span).collect();
+                    builtin.call(values, spans, span)
+                }
+                value => Err(FunError::new(
+                    TypeError::NotCallable(value.type_name().to_string()),
+                    &callee.span,
+                )),
+            },
+            ExprKind::Function(function) => Ok(self.closure(function)),
+            ExprKind::Struct {
+                name,
+                depth,
+                fields,
+            } => {
+                let struct_ = match self.scope_of(*depth).borrow().get(name) {
+                    Some(Value::Struct(struct_)) => struct_,
+                    Some(_) => {
+                        return Err(FunError::new(TypeError::NotAStruct(name.clone()), span));
+                    }
+                    None => {
+                        return Err(FunError::new(
+                            RuntimeError::UndefinedVariable(name.clone()),
+                            span,
+                        ));
+                    }
+                };
+
+                let mut values = vec![None; struct_.fields.len()];
+                for field_value in fields {
+                    let (index, field) = struct_
+                        .field(&field_value.name)
+                        .ok_or_else(|| unknown_field(name, &field_value.name, &field_value.span))?;
+                    let value = self.evaluate(&field_value.value)?;
+                    if !value.is_type(&field.type_) {
+                        return Err(field_mismatch(name, field, &value, &field_value.value.span));
+                    }
+                    values[index] = Some(value);
+                }
+                let fields = values
+                    .into_iter()
+                    .zip(&struct_.fields)
+                    .map(|(value, field)| {
+                        value.ok_or_else(|| {
+                            FunError::new(
+                                TypeError::MissingField {
+                                    struct_: name.clone(),
+                                    field: field.name.clone(),
+                                },
+                                span,
+                            )
+                        })
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(Value::Instance(Rc::new(RefCell::new(Instance {
+                    struct_,
+                    fields,
+                }))))
+            }
+            ExprKind::Field { target, name } => match self.evaluate(target)? {
+                Value::Instance(instance) => {
+                    let instance = instance.borrow();
+                    match instance.struct_.field(name) {
+                        Some((index, _)) => Ok(instance.fields[index].clone()),
+                        None => Err(unknown_field(&instance.struct_.name, name, span)),
+                    }
                 }
-                TokenType::Semicolon => {
-                    self.consume()?;
+                Value::Module(module) => module.member(name).ok_or_else(|| {
+                    FunError::new(
+                        TypeError::UnknownMember {
+                            module: module.name.to_string(),
+                            member: name.clone(),
+                        },
+                        span,
+                    )
+                }),
+                value => Err(unknown_field(&value.type_name(), name, &target.span)),
+            },
+            ExprKind::List(elements) => {
+                let values = elements
+                    .iter()
+                    .map(|element| self.evaluate(element))
+                    .collect::<Result<_, _>>()?;
+                Ok(Value::list(values))
+            }
+            ExprKind::Map(entries) => {
+                let mut map = Map::default();
+                for (key, value) in entries {
+                    let key_value = self.evaluate(key)?;
+                    builtins::check_key(&key_value, &key.span)?;
+                    let value = self.evaluate(value)?;
+                    map.insert(key_value, value);
+                }
+                Ok(Value::map(map))
+            }
+            ExprKind::Index { target, index } => {
+                let target_value = self.evaluate(target)?;
+                let index_value = self.evaluate(index)?;
+                match target_value {
+                    Value::List(list) => {
+                        let int = index_int(index_value, &index.span)?;
+                        let list = list.borrow();
+                        let position = builtins::position(int, list.len(), false, &index.span)?;
+                        Ok(list[position].clone())
+                    }
+                    Value::Map(map) => builtins::lookup(&map.borrow(), &index_value, &index.span),
+                    target_value => Err(not_indexable(&target_value, &target.span)),
                 }
-                _ => {
-                    panic!("Unknown/Unhandled token type: {:?}", token.token_type);
+            }
+            ExprKind::Slice {
+                target,
+                start,
+                end,
+                inclusive,
+            } => {
+                let list = self.evaluate_list(target)?;
+                let start = match start {
+                    Some(start) => Some((self.evaluate_index(start)?, &start.span)),
+                    None => None,
+                };
+                let end = match end {
+                    Some(end) => Some((self.evaluate_index(end)?, &end.span)),
+                    None => None,
+                };
+
+                let list = list.borrow();
+                let length = list.len();
+                let start = match start {
+                    Some((number, span)) => builtins::position(number, length, true, span)?,
+                    None => 0,
+                };
+                let end = match end {
+                    Some((number, span)) if *inclusive => {
+                        builtins::position(number, length, false, span)? + 1
+                    }
+                    Some((number, span)) => builtins::position(number, length, true, span)?,
+                    None => length,
+                };
+                if start > end {
+                    return Err(FunError::new(
+                        RuntimeError::InvalidSlice { start, end },
+                        span,
+                    ));
                 }
+                Ok(Value::list(list[start..end].to_vec()))
             }
         }
+    }
 
-        Ok(())
+    fn closure(&self, function: &Rc<Function>) -> Value {
+        Value::Function(Rc::new(Closure {
+            function: Rc::clone(function),
+            environment: Rc::clone(&self.environment),
+        }))
+    }
+
+    /// Calls `closure`, where `span` is the span of the call expression.
+    /// Functions that return nothing give `none`.
+    fn call(&mut self, closure: &Closure, args: &[Expr], span: &Span) -> Result<Value, FunError> {
+        let function = &closure.function;
+        let name = closure.name();
+
+        if args.len() != function.params.len() {
+            return Err(FunError::new(
+                TypeError::ArityMismatch {
+                    function: name.to_string(),
+                    expected: function.params.len(),
+                    found: args.len(),
+                },
+                span,
+            ));
+        }
+
+        let environment = Environment::child(&closure.environment);
+        for (param, arg) in function.params.iter().zip(args) {
+            let value = self.evaluate(arg)?;
+            let Some(type_) = &param.type_ else {
+                environment.borrow_mut().define(param.name.clone(), value);
+                continue;
+            };
+            if !value.is_type(type_) {
+                return Err(FunError::new(
+                    TypeError::ArgumentMismatch {
+                        function: name.to_string(),
+                        param: param.name.clone(),
+                        expected: type_.to_string(),
+                        found: value.type_name().to_string(),
+                    },
+                    &arg.span,
+                ));
+            }
+            environment
+                .borrow_mut()
+                .define_typed(param.name.clone(), value, type_.clone());
+        }
+
+        if self.call_depth == MAX_CALL_DEPTH {
+            return Err(FunError::new(
+                RuntimeError::StackOverflow {
+                    function: name.to_string(),
+                },
+                span,
+            ));
+        }
+        self.call_depth += 1;
+        let result = self.execute_in(&function.body, environment);
+        self.call_depth -= 1;
+        let (value, returned) = match result? {
+            Flow::Return(value) => (value, true),
+            _ => (Value::None, false),
+        };
+        match &function.return_type {
+            Some(type_) if !value.is_type(type_) => Err(FunError::new(
+                TypeError::ReturnMismatch {
+                    function: name.to_string(),
+                    expected: type_.to_string(),
+                    found: returned.then(|| value.type_name().to_string()),
+                },
+                span,
+            )),
+            _ => Ok(value),
+        }
+    }
+
+    /// Builds the variant at `index` of `enum_` from the values in `args`.
+    fn construct(
+        &mut self,
+        enum_: Rc<Enum>,
+        index: usize,
+        args: &[Expr],
+        span: &Span,
+    ) -> Result<Value, FunError> {
+        let variant = &enum_.variants[index];
+        if args.len() != variant.types.len() {
+            return Err(FunError::new(
+                TypeError::ArityMismatch {
+                    function: variant.name.clone(),
+                    expected: variant.types.len(),
+                    found: args.len(),
+                },
+                span,
+            ));
+        }
+
+        let mut values = Vec::new();
+        for (arg, type_) in args.iter().zip(&variant.types) {
+            let value = self.evaluate(arg)?;
+            if !value.is_type(type_) {
+                return Err(mismatch(type_, value.type_name(), &arg.span));
+            }
+            values.push(value);
+        }
+        Ok(Value::Variant(Rc::new(VariantValue {
+            enum_,
+            index,
+            values,
+        })))
+    }
+
+    fn evaluate_bool(&mut self, expr: &Expr, op: LogicalOp) -> Result<bool, FunError> {
+        match self.evaluate(expr)? {
+            Value::Bool(bool) => Ok(bool),
+            value => Err(invalid_operand(op, value.type_name(), &expr.span)),
+        }
+    }
+}
+
+fn binary(left: Value, op: BinaryOp, right: Value, span: &Span) -> Result<Value, FunError> {
+    // Anything can be compared with `none`
+    let none = matches!(left, Value::None) || matches!(right, Value::None);
+    // Ints and floats are compared by their value
+    let numbers = left.as_float().is_some() && right.as_float().is_some();
+    if none || numbers || left.type_name() == right.type_name() {
+        match op {
+            BinaryOp::Equal => return Ok(Value::Bool(left == right)),
+            BinaryOp::NotEqual => return Ok(Value::Bool(left != right)),
+            _ => {}
+        }
+    }
+
+    match (left, right) {
+        (Value::Int(left), Value::Int(right)) => int_binary(left, op, right, span),
+        (Value::Int(left), Value::Float(right)) => float_binary(left as f64, op, right, span),
+        (Value::Float(left), Value::Int(right)) => float_binary(left, op, right as f64, span),
+        (Value::Float(left), Value::Float(right)) => float_binary(left, op, right, span),
+        (Value::String(left), Value::String(right)) => match op {
+            BinaryOp::Add => Ok(Value::String(left + &right)),
+            _ => compare(Some(left.cmp(&right)), op)
+                .ok_or_else(|| invalid_operands(op, "String", "String", span)),
+        },
+        (left, right) => Err(invalid_operands(
+            op,
+            left.type_name(),
+            right.type_name(),
+            span,
+        )),
+    }
+}
+
+/// Applies `op` to two ints. Division drops the fraction and `%` gives the
+/// remainder of it, so `-7 / 2` is `-3` and `-7 % 2` is `-1`.
+fn int_binary(left: i64, op: BinaryOp, right: i64, span: &Span) -> Result<Value, FunError> {
+    let result = match op {
+        BinaryOp::Add => left.checked_add(right),
+        BinaryOp::Subtract => left.checked_sub(right),
+        BinaryOp::Multiply => left.checked_mul(right),
+        BinaryOp::Divide | BinaryOp::Modulo if right == 0 => {
+            return Err(FunError::new(RuntimeError::DivisionByZero, span));
+        }
+        BinaryOp::Divide => left.checked_div(right),
+        BinaryOp::Modulo => left.checked_rem(right),
+        _ => {
+            return compare(Some(left.cmp(&right)), op)
+                .ok_or_else(|| invalid_operands(op, "Int", "Int", span));
+        }
+    };
+    result.map(Value::Int).ok_or_else(|| overflow(op, span))
+}
+
+/// Applies `op` to two floats, or an int and a float.
+fn float_binary(left: f64, op: BinaryOp, right: f64, span: &Span) -> Result<Value, FunError> {
+    match op {
+        BinaryOp::Add => Ok(Value::Float(left + right)),
+        BinaryOp::Subtract => Ok(Value::Float(left - right)),
+        BinaryOp::Multiply => Ok(Value::Float(left * right)),
+        BinaryOp::Divide | BinaryOp::Modulo if right == 0.0 => {
+            Err(FunError::new(RuntimeError::DivisionByZero, span))
+        }
+        BinaryOp::Divide => Ok(Value::Float(left / right)),
+        BinaryOp::Modulo => Ok(Value::Float(left % right)),
+        _ => compare(left.partial_cmp(&right), op)
+            .ok_or_else(|| invalid_operands(op, "Float", "Float", span)),
+    }
+}
+
+/// The numbers from `start` to `end` by `step`, which is not zero. They are
+/// ints if all three are, floats otherwise.
+fn range(
+    start: Value,
+    end: Value,
+    step: Value,
+    inclusive: bool,
+) -> Box<dyn Iterator<Item = Value>> {
+    let in_range = move |ordering: Ordering, ascending: bool| match ordering {
+        Ordering::Less => ascending,
+        Ordering::Equal => inclusive,
+        Ordering::Greater => !ascending,
+    };
+
+    if let (Value::Int(start), Value::Int(end), Value::Int(step)) = (&start, &end, &step) {
+        let (end, step) = (*end, *step);
+        let mut next = Some(*start);
+        return Box::new(iter::from_fn(move || {
+            let current = next.filter(|current| in_range(current.cmp(&end), step > 0))?;
+            // Stops instead of overflowing past the largest or smallest int
+            next = current.checked_add(step);
+            Some(Value::Int(current))
+        }));
+    }
+
+    let [start, end, step] = [start, end, step].map(|value| {
+        value
+            .as_float()
+            .expect("range bounds are checked to be numbers")
+    });
+    let mut iteration = 0.0;
+    Box::new(iter::from_fn(move || {
+        // Computed from the start each time so a fractional step does not
+        // accumulate rounding errors.
+        let current = start + iteration * step;
+        if !in_range(current.partial_cmp(&end)?, step > 0.0) {
+            return None;
+        }
+        iteration += 1.0;
+        Some(Value::Float(current))
+    }))
+}
+
+/// Applies a comparison operator, or returns `None` if `op` is not one.
+fn compare(ordering: Option<Ordering>, op: BinaryOp) -> Option<Value> {
+    let result = match op {
+        BinaryOp::Less => ordering == Some(Ordering::Less),
+        BinaryOp::LessEqual => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        BinaryOp::Greater => ordering == Some(Ordering::Greater),
+        BinaryOp::GreaterEqual => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        _ => return None,
+    };
+    Some(Value::Bool(result))
+}
+
+fn index_int(index: Value, span: &Span) -> Result<i64, FunError> {
+    match index {
+        Value::Int(int) => Ok(int),
+        index => Err(mismatch("Int", index.type_name(), span)),
+    }
+}
+
+fn overflow(op: impl ToString, span: &Span) -> FunError {
+    FunError::new(RuntimeError::IntegerOverflow(op.to_string()), span)
+}
+
+fn unknown_field(type_: &str, field: &str, span: &Span) -> FunError {
+    FunError::new(
+        TypeError::UnknownField {
+            type_: type_.to_string(),
+            field: field.to_string(),
+        },
+        span,
+    )
+}
+
+fn field_mismatch(struct_: &str, field: &Field, value: &Value, span: &Span) -> FunError {
+    FunError::new(
+        TypeError::FieldMismatch {
+            struct_: struct_.to_string(),
+            field: field.name.clone(),
+            expected: field.type_.to_string(),
+            found: value.type_name().to_string(),
+        },
+        span,
+    )
+}
+
+fn not_indexable(value: &Value, span: &Span) -> FunError {
+    FunError::new(TypeError::NotIndexable(value.type_name().to_string()), span)
+}
+
+fn mismatch(expected: impl ToString, found: impl ToString, span: &Span) -> FunError {
+    FunError::new(
+        TypeError::Mismatch {
+            expected: expected.to_string(),
+            found: found.to_string(),
+        },
+        span,
+    )
+}
+
+fn invalid_operand(op: impl ToString, operand: impl ToString, span: &Span) -> FunError {
+    FunError::new(
+        TypeError::InvalidOperand {
+            op: op.to_string(),
+            operand: operand.to_string(),
+        },
+        span,
+    )
+}
+
+fn invalid_operands(
+    op: BinaryOp,
+    left: impl ToString,
+    right: impl ToString,
+    span: &Span,
+) -> FunError {
+    FunError::new(
+        TypeError::InvalidOperands {
+            op: op.to_string(),
+            left: left.to_string(),
+            right: right.to_string(),
+        },
+        span,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Interpreter;
+    use crate::error::{ErrorKind, RuntimeError};
+
+    /// The value of the expression statement that ends `source`, written as
+    /// the REPL writes it, or the kind of the first error.
+    fn evaluate(source: &str) -> Result<String, ErrorKind> {
+        let program =
+            crate::check("test.fun", source).map_err(|errors| errors[0].kind().clone())?;
+        let (last, rest) = program.split_last().expect("source is not empty");
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .interpret(rest)
+            .and_then(|()| interpreter.interpret_statement(last))
+            .map(|value| value.expect("source ends with an expression").repr())
+            .map_err(|error| error.kind().clone())
+    }
+
+    fn out_of_bounds(index: i64, length: usize) -> Result<String, ErrorKind> {
+        Err(RuntimeError::IndexOutOfBounds { index, length }.into())
+    }
+
+    #[test]
+    fn indexes_count_from_either_end() {
+        let list = "let xs = [1, 2, 3];";
+        assert_eq!(evaluate(&format!("{} xs[0];", list)).as_deref(), Ok("1"));
+        assert_eq!(evaluate(&format!("{} xs[2];", list)).as_deref(), Ok("3"));
+        assert_eq!(evaluate(&format!("{} xs[-1];", list)).as_deref(), Ok("3"));
+        assert_eq!(evaluate(&format!("{} xs[-3];", list)).as_deref(), Ok("1"));
+        assert_eq!(evaluate(&format!("{} xs[3];", list)), out_of_bounds(3, 3));
+        assert_eq!(evaluate(&format!("{} xs[-4];", list)), out_of_bounds(-4, 3));
+        assert_eq!(evaluate("let xs = []; xs[0];"), out_of_bounds(0, 0));
+    }
+
+    #[test]
+    fn index_assignment() {
+        let source = "let xs = [1, 2, 3]; xs[-1] = 4; xs[0] = 5; xs;";
+        assert_eq!(evaluate(source).as_deref(), Ok("[5, 2, 4]"));
+        let source = "let xs = [1, 2, 3]; xs[3] = 4; xs;";
+        assert_eq!(evaluate(source), out_of_bounds(3, 3));
+    }
+
+    #[test]
+    fn slices() {
+        let list = "let xs = [1, 2, 3, 4];";
+        let cases = [
+            ("xs[1..3];", "[2, 3]"),
+            ("xs[1..=2];", "[2, 3]"),
+            ("xs[..2];", "[1, 2]"),
+            ("xs[2..];", "[3, 4]"),
+            ("xs[..];", "[1, 2, 3, 4]"),
+            ("xs[-2..];", "[3, 4]"),
+            ("xs[..-1];", "[1, 2, 3]"),
+            ("xs[4..];", "[]"),
+            ("xs[2..2];", "[]"),
+        ];
+        for (slice, expected) in cases {
+            let source = format!("{} {}", list, slice);
+            assert_eq!(evaluate(&source).as_deref(), Ok(expected), "{}", slice);
+        }
+    }
+
+    #[test]
+    fn slices_out_of_bounds() {
+        let list = "let xs = [1, 2, 3, 4];";
+        assert_eq!(evaluate(&format!("{} xs[..5];", list)), out_of_bounds(5, 4));
+        assert_eq!(
+            evaluate(&format!("{} xs[-5..];", list)),
+            out_of_bounds(-5, 4)
+        );
+        assert_eq!(
+            evaluate(&format!("{} xs[..=4];", list)),
+            out_of_bounds(4, 4)
+        );
+        assert_eq!(
+            evaluate(&format!("{} xs[3..1];", list)),
+            Err(RuntimeError::InvalidSlice { start: 3, end: 1 }.into())
+        );
+    }
+
+    #[test]
+    fn slices_are_copies() {
+        let source = "let xs = [1, 2, 3]; let ys = xs[..]; ys[0] = 4; xs;";
+        assert_eq!(evaluate(source).as_deref(), Ok("[1, 2, 3]"));
+    }
+
+    #[test]
+    fn maps_keep_insertion_order() {
+        let map = r#"let m = {"b": 1, "a": 2}; m["c"] = 3; m["b"] = 4;"#;
+        assert_eq!(
+            evaluate(&format!("{} m;", map)).as_deref(),
+            Ok(r#"{"b": 4, "a": 2, "c": 3}"#)
+        );
+        let source = format!(r#"{} remove(m, "b"); m["b"] = 5; keys(m);"#, map);
+        assert_eq!(evaluate(&source).as_deref(), Ok(r#"["a", "c", "b"]"#));
+        let source = format!(
+            "{} let order = []; for key, value in m {{ push(order, [key, value]); }} order;",
+            map
+        );
+        assert_eq!(
+            evaluate(&source).as_deref(),
+            Ok(r#"[["b", 4], ["a", 2], ["c", 3]]"#)
+        );
+    }
+
+    #[test]
+    fn missing_keys() {
+        let map = r#"let m = {"a": 1};"#;
+        let missing = Err(RuntimeError::MissingKey(r#""b""#.to_string()).into());
+        assert_eq!(evaluate(&format!(r#"{} m["b"];"#, map)), missing);
+        assert_eq!(evaluate(&format!(r#"{} remove(m, "b");"#, map)), missing);
+        assert_eq!(
+            evaluate(&format!(r#"{} get(m, "b");"#, map)).as_deref(),
+            Ok("none")
+        );
+        assert_eq!(
+            evaluate(&format!(r#"{} get(m, "a");"#, map)).as_deref(),
+            Ok("1")
+        );
+    }
+
+    #[test]
+    fn int_division_truncates() {
+        let cases = [
+            ("7 / 2;", "3"),
+            ("-7 / 2;", "-3"),
+            ("7 % 2;", "1"),
+            ("-7 % 2;", "-1"),
+            ("7 % -2;", "1"),
+            ("7 / 2.0;", "3.5"),
+            ("6 / 2;", "3"),
+            ("6.0 / 2;", "3.0"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(evaluate(source).as_deref(), Ok(expected), "{}", source);
+        }
+        for source in ["1 / 0;", "1 % 0;", "1.0 / 0;", "1 % 0.0;"] {
+            assert_eq!(
+                evaluate(source),
+                Err(RuntimeError::DivisionByZero.into()),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn int_overflow() {
+        let max = "let max = 9223372036854775807; let min = -max - 1;";
+        assert_eq!(
+            evaluate(&format!("{} min;", max)).as_deref(),
+            Ok("-9223372036854775808")
+        );
+        let cases = [
+            ("max + 1;", "+"),
+            ("min - 1;", "-"),
+            ("max * 2;", "*"),
+            ("min / -1;", "/"),
+            ("-min;", "-"),
+        ];
+        for (source, op) in cases {
+            assert_eq!(
+                evaluate(&format!("{} {}", max, source)),
+                Err(RuntimeError::IntegerOverflow(op.to_string()).into()),
+                "{}",
+                source
+            );
+        }
+        // Floats are not checked
+        assert_eq!(
+            evaluate(&format!("{} max * 2.0;", max)).as_deref(),
+            Ok("1.8446744073709552e19")
+        );
     }
 }
@@ -0,0 +1,41 @@
+pub mod ast;
+pub mod builtins;
+pub mod checker;
+pub mod environment;
+pub mod error;
+pub mod interpreter;
+pub mod map;
+pub mod parser;
+pub mod resolver;
+pub mod span;
+pub mod tokenizer;
+pub mod value;
+
+use error::FunError;
+
+/// Tokenizes, parses, resolves and type checks `source`, which was read from
+/// `file`, returning the program ready to run.
+///
+/// Syntax errors are all reported together, as are type errors.
+pub fn check(file: &str, source: &str) -> Result<Vec<ast::Stmt>, Vec<FunError>> {
+    let tokens = tokenizer::Tokenizer::new(file, source.to_string())
+        .tokenize()
+        .map_err(|error| vec![error])?;
+    let mut program = parser::Parser::new(tokens).parse()?;
+    resolver::Resolver::new().resolve(&mut program)?;
+    checker::Checker::new().check(&program)?;
+    Ok(program)
+}
+
+/// Checks and then runs `source`, which was read from `file`. Nothing runs
+/// if the program has errors that can be found before running it.
+///
+/// The program runs on a thread of its own, see `interpreter::with_stack`.
+pub fn run(file: &str, source: &str) -> Result<(), Vec<FunError>> {
+    interpreter::with_stack(|| {
+        let program = check(file, source)?;
+        interpreter::Interpreter::new()
+            .interpret(&program)
+            .map_err(|error| vec![error])
+    })
+}
@@ -1,29 +1,48 @@
-use std::io::{Error, ErrorKind};
-
 use std::env::args;
 use std::fs::read_to_string;
 
-mod interpreter;
-mod tokenizer;
-
-fn get_file_path() -> std::io::Result<String> {
-    let args_vec: Vec<String> = args().collect();
-
-    if args_vec.len() < 2 {
-        return Err(Error::new(ErrorKind::InvalidInput, "Not enough arguments"));
-    }
-
-    Ok(args_vec[1].clone())
-}
+mod repl;
 
 fn main() -> std::io::Result<()> {
-    let filepath = get_file_path()?;
-    let content = read_to_string(filepath)?;
-
-    let mut main_tokenizer = tokenizer::Tokenizer::new(content);
-    let tokens = main_tokenizer.tokenize()?;
-    let mut main_interpreter = interpreter::Interpreter::new(tokens.clone());
-
-    main_interpreter.interpret()?;
+    let args: Vec<String> = args().skip(1).collect();
+    let (check_only, filepath) = match args.as_slice() {
+        [] => return fun::interpreter::with_stack(repl::run),
+        [command, filepath] if command == "check" => (true, filepath),
+        [filepath] if filepath != "check" => (false, filepath),
+        _ => {
+            eprintln!("error: Usage: fun [[check] <file>]");
+            std::process::exit(1);
+        }
+    };
+    let content = match read_to_string(filepath) {
+        Ok(content) => content,
+        Err(error) => {
+            eprintln!("error: Cannot read {:?}: {}", filepath, error);
+            std::process::exit(1);
+        }
+    };
+
+    let result = if check_only {
+        fun::interpreter::with_stack(|| fun::check(filepath, &content).map(|_| ()))
+    } else {
+        fun::run(filepath, &content)
+    };
+    if let Err(errors) = result {
+        for error in &errors {
+            eprintln!("{}\n", error.render(&content));
+        }
+        let action = if check_only { "check" } else { "run" };
+        match errors.len() {
+            1 => eprintln!("error: could not {} {} due to 1 error", action, filepath),
+            count => eprintln!(
+                "error: could not {} {} due to {} errors",
+                action, filepath, count
+            ),
+        }
+        std::process::exit(1);
+    }
+    if check_only {
+        println!("No errors found in {}", filepath);
+    }
     Ok(())
 }
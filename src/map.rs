@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use super::value::{self, Value};
+
+/// The values a map can be indexed with, in a form that can be hashed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    /// Also used for floats that are whole numbers, which are equal to ints.
+    Int(i64),
+    /// The bits of the float, with `-0` stored as `0`.
+    Float(u64),
+    String(String),
+    Bool(bool),
+}
+
+impl Key {
+    fn of(value: &Value) -> Option<Key> {
+        match value {
+            Value::Int(int) => Some(Key::Int(*int)),
+            Value::Float(float) => match value::float_to_int(*float) {
+                Some(int) => Some(Key::Int(int)),
+                None => Some(Key::Float((float + 0.0).to_bits())),
+            },
+            Value::String(string) => Some(Key::String(string.clone())),
+            Value::Bool(bool) => Some(Key::Bool(*bool)),
+            _ => None,
+        }
+    }
+}
+
+/// A map that keeps its entries in the order their keys were first inserted.
+#[derive(Debug, Clone, Default)]
+pub struct Map {
+    entries: Vec<(Value, Value)>,
+    /// The index in `entries` of every key.
+    indexes: HashMap<Key, usize>,
+}
+
+impl Map {
+    /// Whether `value` can be used as a key: numbers, strings and booleans.
+    pub fn is_key(value: &Value) -> bool {
+        Key::of(value).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &[(Value, Value)] {
+        &self.entries
+    }
+
+    pub fn get(&self, key: &Value) -> Option<&Value> {
+        let index = self.indexes.get(&Key::of(key)?)?;
+        Some(&self.entries[*index].1)
+    }
+
+    /// Sets the value of `key`, which keeps its position if it is already in
+    /// the map. Returns `false` if `key` cannot be used as a key.
+    pub fn insert(&mut self, key: Value, value: Value) -> bool {
+        let Some(hashed) = Key::of(&key) else {
+            return false;
+        };
+        match self.indexes.get(&hashed) {
+            Some(index) => self.entries[*index].1 = value,
+            None => {
+                self.indexes.insert(hashed, self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+        true
+    }
+
+    pub fn remove(&mut self, key: &Value) -> Option<Value> {
+        let index = self.indexes.remove(&Key::of(key)?)?;
+        let (_, value) = self.entries.remove(index);
+        for later in self.indexes.values_mut() {
+            if *later > index {
+                *later -= 1;
+            }
+        }
+        Some(value)
+    }
+}
+
+impl PartialEq for Map {
+    /// Maps are equal if they have the same entries, in any order.
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .entries
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
@@ -0,0 +1,1178 @@
+use std::rc::Rc;
+
+use super::ast::*;
+use super::error::{FunError, SyntaxError};
+use super::span::Span;
+use super::tokenizer::*;
+
+/// How deeply statements, expressions and types can nest. Checking and
+/// running a program recurse as deeply as it nests, so deeper nesting could
+/// overflow the stack.
+pub const MAX_NESTING: usize = 256;
+
+pub struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+    /// How many statements, expressions and types enclose the one being
+    /// parsed.
+    depth: usize,
+    /// How many loops enclose the statement being parsed.
+    loop_depth: usize,
+    /// How many function bodies enclose the statement being parsed.
+    function_depth: usize,
+    /// Errors of the statements skipped so far.
+    errors: Vec<FunError>,
+    /// Whether `Name {` starts a struct literal. It does not in the header of
+    /// an `if`, `while` or `for`, where the brace starts the body.
+    struct_literals: bool,
+}
+
+impl Parser {
+    pub fn new(tokens: Vec<Token>) -> Self {
+        Self {
+            tokens,
+            index: 0,
+            depth: 0,
+            loop_depth: 0,
+            function_depth: 0,
+            errors: Vec::new(),
+            struct_literals: true,
+        }
+    }
+
+    fn peek(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.index + offset)
+    }
+
+    fn consume(&mut self) -> Result<Token, FunError> {
+        let Some(token) = self.tokens.get(self.index).cloned() else {
+            return Err(FunError::new(
+                SyntaxError::UnexpectedEndOfInput,
+                &self.end_span(),
+            ));
+        };
+        self.index += 1;
+        Ok(token)
+    }
+
+    /// The span of the last consumed token.
+    fn previous_span(&self) -> Span {
+        self.tokens[self.index - 1].span.clone()
+    }
+
+    /// An empty span just past the last token, for errors at end of input.
+    fn end_span(&self) -> Span {
+        let last = &self.tokens[self.tokens.len() - 1].span;
+        Span {
+            start: last.end,
+            column: last.column + (last.end - last.start),
+            ..last.clone()
+        }
+    }
+
+    fn check(&self, token_type: &TokenType) -> bool {
+        matches!(self.peek(0), Some(token) if &token.token_type == token_type)
+    }
+
+    fn expect(&mut self, token_type: TokenType, what: &str) -> Result<Token, FunError> {
+        match self.peek(0) {
+            Some(token) if token.token_type == token_type => self.consume(),
+            Some(token) => Err(expected(what, Some(&token.value), &token.span)),
+            None => Err(expected(what, None, &self.end_span())),
+        }
+    }
+
+    /// Goes one level of nesting deeper, or fails if that is deeper than
+    /// `MAX_NESTING`.
+    fn deeper(&mut self) -> Result<(), FunError> {
+        if self.depth == MAX_NESTING {
+            let span = match self.peek(0) {
+                Some(token) => token.span.clone(),
+                None => self.end_span(),
+            };
+            return Err(FunError::new(SyntaxError::TooDeeplyNested, &span));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Runs `parse` one level of nesting deeper, or fails if that is deeper
+    /// than `MAX_NESTING`. Any deeper levels `parse` goes down are left
+    /// afterwards, also when it fails.
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, FunError>,
+    ) -> Result<T, FunError> {
+        let depth = self.depth;
+        let result = self.deeper().and_then(|()| parse(self));
+        self.depth = depth;
+        result
+    }
+
+    /// Parses the whole program. A statement with an error is skipped so
+    /// the rest can still be checked, and every error found is returned.
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<FunError>> {
+        let mut statements = Vec::new();
+        while self.peek(0).is_some() {
+            if self.check(&TokenType::Semicolon) {
+                self.index += 1;
+                continue;
+            }
+            self.recovering_statement(&mut statements);
+        }
+
+        self.index = 0;
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    /// Parses a statement into `statements`, or records its error and skips
+    /// to where the next statement probably starts.
+    fn recovering_statement(&mut self, statements: &mut Vec<Stmt>) {
+        let start = self.index;
+        match self.statement() {
+            Ok(statement) => statements.push(statement),
+            Err(error) => {
+                // An unclosed block is reported by every block it is nested in
+                if self.errors.last() != Some(&error) {
+                    self.errors.push(error);
+                }
+                // `synchronize` skips a block whole, its brace included
+                if self.index == start && !self.check(&TokenType::OpenBrace) {
+                    self.index += 1;
+                }
+                self.synchronize();
+            }
+        }
+    }
+
+    /// Skips the rest of a statement: up to and including its semicolon or
+    /// braced block, or up to a word that starts a statement or the brace
+    /// closing the enclosing block.
+    fn synchronize(&mut self) {
+        let mut depth = 0;
+        while let Some(token) = self.peek(0) {
+            match token.token_type {
+                TokenType::Semicolon if depth == 0 => {
+                    self.index += 1;
+                    return;
+                }
+                TokenType::OpenBrace => depth += 1,
+                TokenType::CloseBrace if depth == 0 => return,
+                TokenType::CloseBrace => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.index += 1;
+                        return;
+                    }
+                }
+                TokenType::Keyword(
+                    KeywordType::Let
+                    | KeywordType::If
+                    | KeywordType::While
+                    | KeywordType::For
+                    | KeywordType::Fn
+                    | KeywordType::Struct
+                    | KeywordType::Enum
+                    | KeywordType::Match
+                    | KeywordType::Return
+                    | KeywordType::Break
+                    | KeywordType::Continue,
+                ) if depth == 0 => return,
+                TokenType::Ident if depth == 0 && token.value == "print" => return,
+                _ => {}
+            }
+            self.index += 1;
+        }
+    }
+
+    fn statement(&mut self) -> Result<Stmt, FunError> {
+        self.nested(Self::statement_inner)
+    }
+
+    fn statement_inner(&mut self) -> Result<Stmt, FunError> {
+        let token = self.consume()?;
+        let kind = match token.token_type {
+            TokenType::Keyword(KeywordType::Let) => self.let_statement()?,
+            TokenType::Keyword(KeywordType::If) => self.if_statement()?,
+            TokenType::Keyword(KeywordType::While) => self.while_statement()?,
+            TokenType::Keyword(KeywordType::For) => self.for_statement()?,
+            TokenType::Keyword(KeywordType::Match) => self.match_statement()?,
+            TokenType::Keyword(KeywordType::Fn) if self.check(&TokenType::Ident) => {
+                let name = self.consume()?.value;
+                StmtKind::Function(Rc::new(self.function(Some(name), &token.span)?))
+            }
+            TokenType::Keyword(KeywordType::Struct) => {
+                StmtKind::Struct(Rc::new(self.struct_declaration(&token.span)?))
+            }
+            TokenType::Keyword(KeywordType::Enum) => {
+                StmtKind::Enum(Rc::new(self.enum_declaration(&token.span)?))
+            }
+            TokenType::Keyword(KeywordType::Return) => {
+                if self.function_depth == 0 {
+                    return Err(FunError::new(
+                        SyntaxError::ReturnOutsideFunction,
+                        &token.span,
+                    ));
+                }
+                let value = if self.check(&TokenType::Semicolon) {
+                    None
+                } else {
+                    Some(self.expression()?)
+                };
+                self.expect(TokenType::Semicolon, "semicolon")?;
+                StmtKind::Return(value)
+            }
+            TokenType::Keyword(KeywordType::Break) => {
+                if self.loop_depth == 0 {
+                    return Err(FunError::new(SyntaxError::BreakOutsideLoop, &token.span));
+                }
+                self.expect(TokenType::Semicolon, "semicolon")?;
+                StmtKind::Break
+            }
+            TokenType::Keyword(KeywordType::Continue) => {
+                if self.loop_depth == 0 {
+                    return Err(FunError::new(SyntaxError::ContinueOutsideLoop, &token.span));
+                }
+                self.expect(TokenType::Semicolon, "semicolon")?;
+                StmtKind::Continue
+            }
+            TokenType::OpenBrace => StmtKind::Block(self.block()?),
+            TokenType::Ident if token.value == "print" => {
+                if self.check(&TokenType::Assignment) {
+                    return Err(FunError::new(
+                        SyntaxError::NotAssignable(token.value),
+                        &token.span,
+                    ));
+                }
+                let value = self.expression()?;
+                self.expect(TokenType::Semicolon, "semicolon")?;
+                StmtKind::Print(value)
+            }
+            TokenType::Ident if self.check(&TokenType::Assignment) => {
+                self.consume()?;
+                let value = self.expression()?;
+                self.expect(TokenType::Semicolon, "semicolon")?;
+                StmtKind::Assign {
+                    name: token.value,
+                    value,
+                    depth: None,
+                }
+            }
+            _ => {
+                self.index -= 1;
+                let expr = self.expression()?;
+                let kind = match expr.kind {
+                    ExprKind::Index { target, index } if self.check(&TokenType::Assignment) => {
+                        self.consume()?;
+                        StmtKind::SetIndex {
+                            target: *target,
+                            index: *index,
+                            value: self.expression()?,
+                        }
+                    }
+                    ExprKind::Field { target, name } if self.check(&TokenType::Assignment) => {
+                        self.consume()?;
+                        StmtKind::SetField {
+                            target: *target,
+                            name,
+                            value: self.expression()?,
+                        }
+                    }
+                    _ => StmtKind::Expression(expr),
+                };
+                self.expect(TokenType::Semicolon, "semicolon")?;
+                kind
+            }
+        };
+        Ok(Stmt::new(kind, token.span.to(&self.previous_span())))
+    }
+
+    /// Parses the rest of a function after `fn` and its name, if any. `start`
+    /// is the span of `fn`.
+    fn function(&mut self, name: Option<String>, start: &Span) -> Result<Function, FunError> {
+        self.expect(TokenType::OpenParen, "open paren")?;
+        let mut params = Vec::new();
+        while !self.check(&TokenType::CloseParen) {
+            if !params.is_empty() {
+                self.expect(TokenType::Comma, "comma")?;
+            }
+            let param_name = self.expect(TokenType::Ident, "parameter name")?;
+            let type_ = if self.check(&TokenType::Colon) {
+                self.consume()?;
+                Some(self.type_annotation()?)
+            } else {
+                None
+            };
+            params.push(Param {
+                name: param_name.value,
+                type_,
+                span: param_name.span.to(&self.previous_span()),
+            });
+        }
+        self.consume()?;
+
+        let return_type = if self.check(&TokenType::Colon) {
+            self.consume()?;
+            Some(self.type_annotation()?)
+        } else {
+            None
+        };
+        let span = start.to(&self.previous_span());
+
+        self.expect(TokenType::OpenBrace, "open brace")?;
+        // `break` and `continue` cannot reach a loop outside of the function
+        let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        self.function_depth += 1;
+        let body = self.block();
+        self.function_depth -= 1;
+        self.loop_depth = loop_depth;
+
+        Ok(Function {
+            name,
+            params,
+            return_type,
+            body: body?,
+            span,
+        })
+    }
+
+    /// Parses the rest of a struct declaration after `struct`, whose span is
+    /// `start`.
+    fn struct_declaration(&mut self, start: &Span) -> Result<Struct, FunError> {
+        let name = self.expect(TokenType::Ident, "struct name")?.value;
+        let span = start.to(&self.previous_span());
+        self.expect(TokenType::OpenBrace, "open brace")?;
+
+        let fields = self.braced(|parser| {
+            let mut fields: Vec<Field> = Vec::new();
+            while !parser.check(&TokenType::CloseBrace) {
+                let field_name = parser.expect(TokenType::Ident, "field name")?;
+                if fields.iter().any(|field| field.name == field_name.value) {
+                    return Err(FunError::new(
+                        SyntaxError::DuplicateField(field_name.value),
+                        &field_name.span,
+                    ));
+                }
+                parser.expect(TokenType::Colon, "colon")?;
+                let type_ = parser.type_annotation()?;
+                fields.push(Field {
+                    name: field_name.value,
+                    type_,
+                    span: field_name.span.to(&parser.previous_span()),
+                });
+                if !parser.check(&TokenType::CloseBrace) {
+                    parser.expect(TokenType::Comma, "comma")?;
+                }
+            }
+            Ok(fields)
+        })?;
+        Ok(Struct { name, fields, span })
+    }
+
+    /// Parses the rest of an enum declaration after `enum`, whose span is
+    /// `start`.
+    fn enum_declaration(&mut self, start: &Span) -> Result<Enum, FunError> {
+        let name = self.expect(TokenType::Ident, "enum name")?.value;
+        let span = start.to(&self.previous_span());
+        self.expect(TokenType::OpenBrace, "open brace")?;
+
+        let variants = self.braced(|parser| {
+            let mut variants: Vec<Variant> = Vec::new();
+            while !parser.check(&TokenType::CloseBrace) {
+                let variant_name = parser.expect(TokenType::Ident, "variant name")?;
+                if variants
+                    .iter()
+                    .any(|variant| variant.name == variant_name.value)
+                {
+                    return Err(FunError::new(
+                        SyntaxError::DuplicateVariant(variant_name.value),
+                        &variant_name.span,
+                    ));
+                }
+                let mut types = Vec::new();
+                if parser.check(&TokenType::OpenParen) {
+                    parser.consume()?;
+                    while !parser.check(&TokenType::CloseParen) {
+                        if !types.is_empty() {
+                            parser.expect(TokenType::Comma, "comma")?;
+                        }
+                        types.push(parser.type_annotation()?);
+                    }
+                    parser.consume()?;
+                }
+                variants.push(Variant {
+                    name: variant_name.value,
+                    types,
+                    span: variant_name.span.to(&parser.previous_span()),
+                });
+                if !parser.check(&TokenType::CloseBrace) {
+                    parser.expect(TokenType::Comma, "comma")?;
+                }
+            }
+            Ok(variants)
+        })?;
+        Ok(Enum {
+            name,
+            variants,
+            span,
+        })
+    }
+
+    fn match_statement(&mut self) -> Result<StmtKind, FunError> {
+        let value = self.header_expression()?;
+        self.expect(TokenType::OpenBrace, "open brace")?;
+
+        let arms = self.braced(|parser| {
+            let mut arms = Vec::new();
+            while !parser.check(&TokenType::CloseBrace) {
+                arms.push(parser.arm()?);
+            }
+            Ok(arms)
+        })?;
+        Ok(StmtKind::Match { value, arms })
+    }
+
+    /// Parses what is between braces with `f`, after the opening brace, and
+    /// then the closing brace. After an error, skips past the closing brace,
+    /// so the rest of what is between them is not parsed as statements.
+    fn braced<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, FunError>,
+    ) -> Result<T, FunError> {
+        let result = f(self);
+        if result.is_ok() {
+            self.consume()?;
+            return result;
+        }
+
+        let mut depth = 0;
+        while let Ok(token) = self.consume() {
+            match token.token_type {
+                TokenType::OpenBrace => depth += 1,
+                TokenType::CloseBrace if depth == 0 => break,
+                TokenType::CloseBrace => depth -= 1,
+                _ => {}
+            }
+        }
+        result
+    }
+
+    fn arm(&mut self) -> Result<Arm, FunError> {
+        let start = match self.peek(0) {
+            Some(token) => token.span.clone(),
+            None => return Err(expected("close brace", None, &self.end_span())),
+        };
+        let pattern = self.pattern()?;
+        let guard = if self.check(&TokenType::Keyword(KeywordType::If)) {
+            self.consume()?;
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        let span = start.to(&self.previous_span());
+        self.expect(TokenType::FatArrow, "'=>'")?;
+
+        // An arm runs a block or a single statement
+        let body = if self.check(&TokenType::OpenBrace) {
+            self.consume()?;
+            self.block()?
+        } else {
+            vec![self.statement()?]
+        };
+        if self.check(&TokenType::Comma) {
+            self.consume()?;
+        }
+        Ok(Arm {
+            pattern,
+            guard,
+            body,
+            span,
+        })
+    }
+
+    /// Parses `_`, `Name` or `Name(a, _, c)`.
+    fn pattern(&mut self) -> Result<Pattern, FunError> {
+        let name = self.expect(TokenType::Ident, "pattern")?;
+        if name.value == "_" {
+            return Ok(Pattern::Wildcard);
+        }
+
+        let mut bindings: Vec<Option<String>> = Vec::new();
+        if self.check(&TokenType::OpenParen) {
+            self.consume()?;
+            while !self.check(&TokenType::CloseParen) {
+                if !bindings.is_empty() {
+                    self.expect(TokenType::Comma, "comma")?;
+                }
+                let binding = self.expect(TokenType::Ident, "identifier")?;
+                bindings.push((binding.value != "_").then_some(binding.value));
+            }
+            self.consume()?;
+        }
+        Ok(Pattern::Variant {
+            name: name.value,
+            bindings,
+        })
+    }
+
+    fn if_statement(&mut self) -> Result<StmtKind, FunError> {
+        let condition = self.header_expression()?;
+        self.expect(TokenType::OpenBrace, "open brace")?;
+        let then_branch = self.block()?;
+
+        let else_branch = if self.check(&TokenType::Keyword(KeywordType::Else)) {
+            self.consume()?;
+            let start = self.peek(0).map(|token| token.span.clone());
+            let kind = if self.check(&TokenType::Keyword(KeywordType::If)) {
+                self.consume()?;
+                self.if_statement()?
+            } else {
+                self.expect(TokenType::OpenBrace, "open brace")?;
+                StmtKind::Block(self.block()?)
+            };
+            let span = start
+                .expect("else is followed by a token")
+                .to(&self.previous_span());
+            Some(Box::new(Stmt::new(kind, span)))
+        } else {
+            None
+        };
+
+        Ok(StmtKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    fn while_statement(&mut self) -> Result<StmtKind, FunError> {
+        let condition = self.header_expression()?;
+        self.expect(TokenType::OpenBrace, "open brace")?;
+        let body = self.loop_body()?;
+        Ok(StmtKind::While { condition, body })
+    }
+
+    fn for_statement(&mut self) -> Result<StmtKind, FunError> {
+        let first = self.expect(TokenType::Ident, "identifier")?;
+        let (key, variable) = if self.check(&TokenType::Comma) {
+            self.consume()?;
+            let variable = self.expect(TokenType::Ident, "identifier")?.value;
+            (Some(first.value.clone()), variable)
+        } else {
+            (None, first.value.clone())
+        };
+        self.expect(TokenType::Keyword(KeywordType::In), "'in'")?;
+
+        let start = self.header_expression()?;
+        let inclusive = match self.peek(0).map(|token| &token.token_type) {
+            Some(TokenType::DotDot) => Some(false),
+            Some(TokenType::DotDotEqual) => Some(true),
+            _ => None,
+        };
+        let iterable = match inclusive {
+            Some(_) if key.is_some() => {
+                let span = first.span.to(&self.previous_span());
+                return Err(FunError::new(SyntaxError::KeyedRange, &span));
+            }
+            Some(inclusive) => {
+                self.consume()?;
+                let end = self.header_expression()?;
+                let step = if self.check(&TokenType::Keyword(KeywordType::Step)) {
+                    self.consume()?;
+                    Some(Box::new(self.header_expression()?))
+                } else {
+                    None
+                };
+                Iterable::Range {
+                    start,
+                    end,
+                    inclusive,
+                    step,
+                }
+            }
+            None => Iterable::Expr(start),
+        };
+
+        self.expect(TokenType::OpenBrace, "open brace")?;
+        let body = self.loop_body()?;
+        Ok(StmtKind::For {
+            key,
+            variable,
+            iterable,
+            body,
+        })
+    }
+
+    fn loop_body(&mut self) -> Result<Vec<Stmt>, FunError> {
+        self.loop_depth += 1;
+        let body = self.block();
+        self.loop_depth -= 1;
+        body
+    }
+
+    /// Parses the statements of a block whose opening brace was already consumed.
+    fn block(&mut self) -> Result<Vec<Stmt>, FunError> {
+        let mut statements = Vec::new();
+        while !self.check(&TokenType::CloseBrace) {
+            if self.peek(0).is_none() {
+                return Err(expected("close brace", None, &self.end_span()));
+            }
+            if self.check(&TokenType::Semicolon) {
+                self.consume()?;
+                continue;
+            }
+            self.recovering_statement(&mut statements);
+        }
+        self.consume()?;
+        Ok(statements)
+    }
+
+    fn let_statement(&mut self) -> Result<StmtKind, FunError> {
+        let name_token = self.expect(TokenType::Ident, "identifier")?;
+        let name = name_token.value;
+
+        let type_ = if self.check(&TokenType::Colon) {
+            self.consume()?;
+            Some(self.type_annotation()?)
+        } else {
+            None
+        };
+
+        let value = if self.check(&TokenType::Assignment) {
+            self.consume()?;
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        if type_.is_none() && value.is_none() {
+            return Err(FunError::new(
+                SyntaxError::MissingTypeOrValue(name),
+                &name_token.span,
+            ));
+        }
+
+        self.expect(TokenType::Semicolon, "semicolon")?;
+        Ok(StmtKind::Let { name, type_, value })
+    }
+
+    fn type_annotation(&mut self) -> Result<Type, FunError> {
+        self.nested(Self::type_annotation_inner)
+    }
+
+    fn type_annotation_inner(&mut self) -> Result<Type, FunError> {
+        if self.check(&TokenType::Keyword(KeywordType::Fn)) {
+            self.consume()?;
+            self.expect(TokenType::OpenParen, "open paren")?;
+            let mut params = Vec::new();
+            while !self.check(&TokenType::CloseParen) {
+                if !params.is_empty() {
+                    self.expect(TokenType::Comma, "comma")?;
+                }
+                params.push(self.type_annotation()?);
+            }
+            self.consume()?;
+
+            let return_type = if self.check(&TokenType::Colon) {
+                self.consume()?;
+                Some(Box::new(self.type_annotation()?))
+            } else {
+                None
+            };
+            // With a return type, a trailing `?` belongs to the return type
+            return self.optional(Type::Function {
+                params,
+                return_type,
+            });
+        }
+
+        if self.check(&TokenType::Ident) {
+            let name = self.consume()?.value;
+            return self.optional(Type::Named(name));
+        }
+
+        let token = self.expect(TokenType::Type, "type")?;
+        let type_ = match token.value.as_str() {
+            "string" => Type::String,
+            "int" => Type::Int,
+            "float" => Type::Float,
+            "number" => Type::Number,
+            "bool" => Type::Bool,
+            "list" => {
+                self.expect(TokenType::Less, "'<'")?;
+                let element = self.type_annotation()?;
+                self.expect(TokenType::Greater, "'>'")?;
+                Type::List(Box::new(element))
+            }
+            "map" => {
+                self.expect(TokenType::Less, "'<'")?;
+                let key = self.type_annotation()?;
+                self.expect(TokenType::Comma, "comma")?;
+                let value = self.type_annotation()?;
+                self.expect(TokenType::Greater, "'>'")?;
+                Type::Map(Box::new(key), Box::new(value))
+            }
+            _ => {
+                return Err(FunError::new(
+                    SyntaxError::UnknownType(token.value),
+                    &token.span,
+                ));
+            }
+        };
+        self.optional(type_)
+    }
+
+    /// Wraps `type_` in `Type::Optional` if it is followed by `?`.
+    fn optional(&mut self, type_: Type) -> Result<Type, FunError> {
+        if self.check(&TokenType::Question) {
+            self.consume()?;
+            return Ok(Type::Optional(Box::new(type_)));
+        }
+        Ok(type_)
+    }
+
+    fn expression(&mut self) -> Result<Expr, FunError> {
+        self.nested(Self::or)
+    }
+
+    /// Parses an expression followed by a braced body, where a struct literal
+    /// is only allowed inside brackets.
+    fn header_expression(&mut self) -> Result<Expr, FunError> {
+        let struct_literals = std::mem::replace(&mut self.struct_literals, false);
+        let expr = self.expression();
+        self.struct_literals = struct_literals;
+        expr
+    }
+
+    /// Parses an expression inside brackets, where struct literals are
+    /// allowed again.
+    fn nested_expression(&mut self) -> Result<Expr, FunError> {
+        let struct_literals = std::mem::replace(&mut self.struct_literals, true);
+        let expr = self.expression();
+        self.struct_literals = struct_literals;
+        expr
+    }
+
+    fn or(&mut self) -> Result<Expr, FunError> {
+        let mut left = self.and()?;
+        // Every operator nests the operations before it one level deeper
+        let depth = self.depth;
+        while matches!(
+            self.peek(0).map(|token| &token.token_type),
+            Some(TokenType::Keyword(KeywordType::Or) | TokenType::OrOr)
+        ) {
+            self.deeper()?;
+            self.consume()?;
+            let right = self.and()?;
+            left = logical(left, LogicalOp::Or, right);
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, FunError> {
+        let mut left = self.equality()?;
+        // Every operator nests the operations before it one level deeper
+        let depth = self.depth;
+        while matches!(
+            self.peek(0).map(|token| &token.token_type),
+            Some(TokenType::Keyword(KeywordType::And) | TokenType::AndAnd)
+        ) {
+            self.deeper()?;
+            self.consume()?;
+            let right = self.equality()?;
+            left = logical(left, LogicalOp::And, right);
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn equality(&mut self) -> Result<Expr, FunError> {
+        let mut left = self.comparison()?;
+        let depth = self.depth;
+        loop {
+            let op = match self.peek(0).map(|token| &token.token_type) {
+                Some(TokenType::EqualEqual) => BinaryOp::Equal,
+                Some(TokenType::BangEqual) => BinaryOp::NotEqual,
+                _ => break,
+            };
+            self.deeper()?;
+            self.consume()?;
+            let right = self.comparison()?;
+            left = binary(left, op, right);
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, FunError> {
+        let mut left = self.term()?;
+        let depth = self.depth;
+        loop {
+            let op = match self.peek(0).map(|token| &token.token_type) {
+                Some(TokenType::Less) => BinaryOp::Less,
+                Some(TokenType::LessEqual) => BinaryOp::LessEqual,
+                Some(TokenType::Greater) => BinaryOp::Greater,
+                Some(TokenType::GreaterEqual) => BinaryOp::GreaterEqual,
+                _ => break,
+            };
+            self.deeper()?;
+            self.consume()?;
+            let right = self.term()?;
+            left = binary(left, op, right);
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn term(&mut self) -> Result<Expr, FunError> {
+        let mut left = self.factor()?;
+        let depth = self.depth;
+        loop {
+            let op = match self.peek(0).map(|token| &token.token_type) {
+                Some(TokenType::Plus) => BinaryOp::Add,
+                Some(TokenType::Minus) => BinaryOp::Subtract,
+                _ => break,
+            };
+            self.deeper()?;
+            self.consume()?;
+            let right = self.factor()?;
+            left = binary(left, op, right);
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn factor(&mut self) -> Result<Expr, FunError> {
+        let mut left = self.unary()?;
+        let depth = self.depth;
+        loop {
+            let op = match self.peek(0).map(|token| &token.token_type) {
+                Some(TokenType::Star) => BinaryOp::Multiply,
+                Some(TokenType::Slash) => BinaryOp::Divide,
+                Some(TokenType::Percent) => BinaryOp::Modulo,
+                _ => break,
+            };
+            self.deeper()?;
+            self.consume()?;
+            let right = self.unary()?;
+            left = binary(left, op, right);
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, FunError> {
+        let op = match self.peek(0).map(|token| &token.token_type) {
+            Some(TokenType::Minus) => UnaryOp::Negate,
+            Some(TokenType::Bang | TokenType::Keyword(KeywordType::Not)) => UnaryOp::Not,
+            _ => return self.call(),
+        };
+        let start = self.consume()?.span;
+        let expr = self.nested(Self::unary)?;
+        let span = start.to(&expr.span);
+        Ok(Expr::new(
+            ExprKind::Unary {
+                op,
+                expr: Box::new(expr),
+            },
+            span,
+        ))
+    }
+
+    /// Parses calls `f(x)`, indexing `xs[i]`, slicing `xs[a..b]` and field
+    /// access `p.x`.
+    fn call(&mut self) -> Result<Expr, FunError> {
+        let mut expr = self.primary()?;
+        let depth = self.depth;
+        loop {
+            let start = expr.span.clone();
+            if matches!(
+                self.peek(0).map(|token| &token.token_type),
+                Some(TokenType::OpenParen | TokenType::OpenBracket | TokenType::Dot)
+            ) {
+                self.deeper()?;
+            }
+            let kind = if self.check(&TokenType::OpenParen) {
+                self.consume()?;
+                let mut args = Vec::new();
+                while !self.check(&TokenType::CloseParen) {
+                    if !args.is_empty() {
+                        self.expect(TokenType::Comma, "comma")?;
+                    }
+                    args.push(self.nested_expression()?);
+                }
+                self.consume()?;
+                ExprKind::Call {
+                    callee: Box::new(expr),
+                    args,
+                }
+            } else if self.check(&TokenType::OpenBracket) {
+                self.consume()?;
+                let kind = self.index(expr)?;
+                self.expect(TokenType::CloseBracket, "close bracket")?;
+                kind
+            } else if self.check(&TokenType::Dot) {
+                self.consume()?;
+                let name = self.expect(TokenType::Ident, "field name")?.value;
+                ExprKind::Field {
+                    target: Box::new(expr),
+                    name,
+                }
+            } else {
+                self.depth = depth;
+                return Ok(expr);
+            };
+            expr = Expr::new(kind, start.to(&self.previous_span()));
+        }
+    }
+
+    /// Parses what is between the brackets after `target`.
+    fn index(&mut self, target: Expr) -> Result<ExprKind, FunError> {
+        let start = if self.check(&TokenType::DotDot) || self.check(&TokenType::DotDotEqual) {
+            None
+        } else {
+            Some(Box::new(self.nested_expression()?))
+        };
+        let inclusive = match self.peek(0).map(|token| &token.token_type) {
+            Some(TokenType::DotDot) => false,
+            Some(TokenType::DotDotEqual) => true,
+            _ => {
+                return Ok(ExprKind::Index {
+                    target: Box::new(target),
+                    index: start.expect("a missing start is followed by a range"),
+                });
+            }
+        };
+        self.consume()?;
+        let end = if self.check(&TokenType::CloseBracket) && !inclusive {
+            None
+        } else {
+            Some(Box::new(self.nested_expression()?))
+        };
+        Ok(ExprKind::Slice {
+            target: Box::new(target),
+            start,
+            end,
+            inclusive,
+        })
+    }
+
+    fn primary(&mut self) -> Result<Expr, FunError> {
+        let token = self.consume()?;
+        let kind = match token.token_type {
+            TokenType::Literal(LiteralType::Number) => {
+                let number = if token.value.contains(['.', 'e', 'E']) {
+                    token.value.parse().map(ExprKind::Float).ok()
+                } else {
+                    token.value.parse().map(ExprKind::Int).ok()
+                };
+                match number {
+                    Some(number) => number,
+                    None => {
+                        return Err(FunError::new(
+                            SyntaxError::InvalidNumber(token.value),
+                            &token.span,
+                        ));
+                    }
+                }
+            }
+            TokenType::Literal(LiteralType::String) => ExprKind::String(token.value),
+            TokenType::StringPart => {
+                let mut parts = Vec::new();
+                let mut text = token.clone();
+                loop {
+                    if !text.value.is_empty() {
+                        parts.push(Expr::new(ExprKind::String(text.value), text.span));
+                    }
+                    if text.token_type == TokenType::StringEnd {
+                        break;
+                    }
+                    parts.push(self.nested_expression()?);
+                    self.expect(TokenType::InterpolationEnd, "'}'")?;
+                    // The tokenizer puts more text after every `}`
+                    text = self.consume()?;
+                }
+                ExprKind::Interpolation(parts)
+            }
+            TokenType::Keyword(KeywordType::True) => ExprKind::Bool(true),
+            TokenType::Keyword(KeywordType::None) => ExprKind::None,
+            TokenType::Keyword(KeywordType::False) => ExprKind::Bool(false),
+            TokenType::Keyword(KeywordType::Fn) => {
+                ExprKind::Function(Rc::new(self.function(None, &token.span)?))
+            }
+            TokenType::Ident if self.struct_literals && self.check(&TokenType::OpenBrace) => {
+                self.consume()?;
+                let fields = self.braced(|parser| {
+                    let mut fields: Vec<FieldValue> = Vec::new();
+                    while !parser.check(&TokenType::CloseBrace) {
+                        let name = parser.expect(TokenType::Ident, "field name")?;
+                        if fields.iter().any(|field| field.name == name.value) {
+                            return Err(FunError::new(
+                                SyntaxError::DuplicateField(name.value),
+                                &name.span,
+                            ));
+                        }
+                        parser.expect(TokenType::Colon, "colon")?;
+                        fields.push(FieldValue {
+                            name: name.value,
+                            value: parser.nested_expression()?,
+                            span: name.span,
+                        });
+                        if !parser.check(&TokenType::CloseBrace) {
+                            parser.expect(TokenType::Comma, "comma")?;
+                        }
+                    }
+                    Ok(fields)
+                })?;
+                ExprKind::Struct {
+                    name: token.value,
+                    depth: None,
+                    fields,
+                }
+            }
+            TokenType::Ident => ExprKind::Variable {
+                name: token.value,
+                depth: None,
+            },
+            TokenType::OpenParen => {
+                let expr = self.nested_expression()?;
+                self.expect(TokenType::CloseParen, "close paren")?;
+                expr.kind
+            }
+            TokenType::OpenBracket => {
+                let mut elements = Vec::new();
+                while !self.check(&TokenType::CloseBracket) {
+                    elements.push(self.nested_expression()?);
+                    if !self.check(&TokenType::CloseBracket) {
+                        self.expect(TokenType::Comma, "comma")?;
+                    }
+                }
+                self.consume()?;
+                ExprKind::List(elements)
+            }
+            TokenType::OpenBrace => {
+                let mut entries = Vec::new();
+                while !self.check(&TokenType::CloseBrace) {
+                    let key = self.nested_expression()?;
+                    self.expect(TokenType::Colon, "colon")?;
+                    entries.push((key, self.nested_expression()?));
+                    if !self.check(&TokenType::CloseBrace) {
+                        self.expect(TokenType::Comma, "comma")?;
+                    }
+                }
+                self.consume()?;
+                ExprKind::Map(entries)
+            }
+            _ => {
+                // Left for `synchronize`, which may stop at it
+                self.index -= 1;
+                return Err(expected("expression", Some(&token.value), &token.span));
+            }
+        };
+        Ok(Expr::new(kind, token.span.to(&self.previous_span())))
+    }
+}
+
+fn expected(what: &str, found: Option<&str>, span: &Span) -> FunError {
+    FunError::new(
+        SyntaxError::Expected {
+            expected: what.to_string(),
+            found: found.map(str::to_string),
+        },
+        span,
+    )
+}
+
+fn binary(left: Expr, op: BinaryOp, right: Expr) -> Expr {
+    let span = left.span.to(&right.span);
+    Expr::new(
+        ExprKind::Binary {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        },
+        span,
+    )
+}
+
+fn logical(left: Expr, op: LogicalOp, right: Expr) -> Expr {
+    let span = left.span.to(&right.span);
+    Expr::new(
+        ExprKind::Logical {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        },
+        span,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MAX_NESTING;
+    use crate::error::{ErrorKind, SyntaxError};
+    use crate::interpreter::with_stack;
+
+    /// The kinds of the errors found in `source`, in the order found.
+    fn errors(source: &str) -> Vec<ErrorKind> {
+        with_stack(|| match crate::check("test.fun", source) {
+            Ok(_) => Vec::new(),
+            Err(errors) => errors.iter().map(|error| error.kind().clone()).collect(),
+        })
+    }
+
+    fn too_deep() -> ErrorKind {
+        SyntaxError::TooDeeplyNested.into()
+    }
+
+    #[test]
+    fn duplicate_field_in_struct_literal() {
+        let source = "
+            struct P { x: number }
+            let p = P { x: 1, x: 2 };
+            print p;
+        ";
+        assert_eq!(
+            errors(source),
+            [SyntaxError::DuplicateField("x".to_string()).into()]
+        );
+    }
+
+    #[test]
+    fn nesting_up_to_the_limit() {
+        let depth = MAX_NESTING - 10;
+        let parens = format!("print {}1{};", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(errors(&parens), []);
+        let sum = format!("print {};", vec!["1"; depth].join(" + "));
+        assert_eq!(errors(&sum), []);
+        let calls = format!("fn f() {{ return f; }} print f{};", "()".repeat(depth));
+        assert_eq!(errors(&calls), []);
+    }
+
+    #[test]
+    fn nesting_past_the_limit() {
+        let parens = format!("print {}1{};", "(".repeat(3000), ")".repeat(3000));
+        assert_eq!(errors(&parens), [too_deep()]);
+        let negations = format!("print {}1;", "-".repeat(3000));
+        assert_eq!(errors(&negations), [too_deep()]);
+        let blocks = format!("{}print 1;{}", "{ ".repeat(3000), " }".repeat(3000));
+        assert_eq!(errors(&blocks), [too_deep()]);
+    }
+
+    #[test]
+    fn long_flat_chains() {
+        let sum = format!("print {};", vec!["1"; 200_000].join(" + "));
+        assert_eq!(errors(&sum), [too_deep()]);
+        let conditions = format!("print {};", vec!["true"; 3000].join(" and "));
+        assert_eq!(errors(&conditions), [too_deep()]);
+        let calls = format!("fn f() {{ return f; }} print f{};", "()".repeat(3000));
+        assert_eq!(errors(&calls), [too_deep()]);
+        let fields = format!("let xs = [[1]]; print xs{};", "[0]".repeat(3000));
+        assert_eq!(errors(&fields), [too_deep()]);
+    }
+}
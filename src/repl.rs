@@ -0,0 +1,190 @@
+use std::fs::read_to_string;
+use std::io::{self, Write};
+
+use fun::ast::Stmt;
+use fun::checker::Checker;
+use fun::error::{ErrorKind, FunError, LexicalError};
+use fun::interpreter::Interpreter;
+use fun::parser::Parser;
+use fun::resolver::Resolver;
+use fun::tokenizer::{TokenType, Tokenizer};
+use fun::value::Value;
+
+const HELP: &str = "\
+Statements run as soon as they are complete. An expression statement such as
+`1 + 2;` prints its value, unless it is none. Input continues on the next
+line while a brace or bracket is open or the statement has no semicolon yet;
+an empty line runs it anyway.
+
+:help          Show this message
+:vars          List the global variables
+:reset         Forget all variables and functions
+:load <file>   Run a file in this session
+:quit          Exit, as does Ctrl-D";
+
+/// What the REPL keeps between entries.
+struct Session {
+    resolver: Resolver,
+    checker: Checker,
+    interpreter: Interpreter,
+}
+
+impl Session {
+    fn new() -> Self {
+        Self {
+            resolver: Resolver::new(),
+            checker: Checker::new(),
+            interpreter: Interpreter::new(),
+        }
+    }
+
+    /// Runs `source`, printing the value of each expression statement if
+    /// `echo` is set. If it fails, only what its statements that ran declared
+    /// stays declared.
+    fn run(&mut self, file: &str, source: &str, echo: bool) -> Result<(), Vec<FunError>> {
+        let tokens = Tokenizer::new(file, source.to_string())
+            .tokenize()
+            .map_err(|error| vec![error])?;
+        let mut program = Parser::new(tokens).parse()?;
+        let (resolver, checker) = (self.resolver.clone(), self.checker.clone());
+        if let Err(errors) = self.declare(&mut program) {
+            (self.resolver, self.checker) = (resolver, checker);
+            return Err(errors);
+        }
+
+        for (index, statement) in program.iter().enumerate() {
+            let value = match self.interpreter.interpret_statement(statement) {
+                Ok(value) => value,
+                Err(error) => {
+                    (self.resolver, self.checker) = (resolver, checker);
+                    // The statements before it were declared without errors
+                    // as part of the whole entry, so they are again
+                    let _ = self.declare(&mut program[..index]);
+                    return Err(vec![error]);
+                }
+            };
+            match value {
+                // Calls of functions that return nothing give `none`
+                Some(Value::None) | None => {}
+                Some(value) if echo => println!("{}", value.repr()),
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolves and checks `program`, keeping what it declares.
+    fn declare(&mut self, program: &mut [Stmt]) -> Result<(), Vec<FunError>> {
+        self.resolver.resolve(program)?;
+        self.checker.check(program)
+    }
+
+    /// Runs a `:` command. Returns `false` if the REPL should exit.
+    fn command(&mut self, command: &str) -> bool {
+        let (name, argument) = match command.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (command, ""),
+        };
+
+        match name {
+            ":help" => println!("{}", HELP),
+            ":vars" => {
+                for (name, value) in self.interpreter.globals() {
+                    println!("{}: {} = {}", name, value.type_name(), value.repr());
+                }
+            }
+            ":reset" => *self = Session::new(),
+            ":load" if argument.is_empty() => eprintln!("error: Usage: :load <file>"),
+            ":load" => match read_to_string(argument) {
+                Ok(source) => {
+                    if let Err(errors) = self.run(argument, &source, false) {
+                        report(&errors, &source);
+                    }
+                }
+                Err(error) => eprintln!("error: Cannot read {:?}: {}", argument, error),
+            },
+            ":quit" => return false,
+            _ => eprintln!("error: Unknown command {:?}, try :help", name),
+        }
+        true
+    }
+}
+
+/// Reads entries from stdin and runs them until `:quit` or the end of input.
+pub fn run() -> io::Result<()> {
+    println!("Fun REPL, type :help for help");
+    let mut session = Session::new();
+    let mut source = String::new();
+
+    loop {
+        print!("{}", if source.is_empty() { "> " } else { "... " });
+        io::stdout().flush()?;
+
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line)? == 0 {
+            println!();
+            return Ok(());
+        }
+
+        let blank = line.trim().is_empty();
+        if source.is_empty() {
+            if blank {
+                continue;
+            }
+            if line.trim_start().starts_with(':') {
+                if !session.command(line.trim()) {
+                    return Ok(());
+                }
+                continue;
+            }
+        }
+
+        source.push_str(&line);
+        if !blank && is_incomplete(&source) {
+            continue;
+        }
+        if let Err(errors) = session.run("<repl>", &source, true) {
+            report(&errors, &source);
+        }
+        source.clear();
+    }
+}
+
+/// Whether `source` needs more lines: a string or bracket of any kind is still
+/// open, or the last statement has not ended.
+fn is_incomplete(source: &str) -> bool {
+    let tokens = match Tokenizer::new("<repl>", source.to_string()).tokenize() {
+        Ok(tokens) => tokens,
+        Err(error) => {
+            return matches!(
+                error.kind(),
+                ErrorKind::Lexical(LexicalError::UnterminatedString)
+            );
+        }
+    };
+
+    let mut depth = 0;
+    for token in &tokens {
+        match token.token_type {
+            TokenType::OpenBrace | TokenType::OpenParen | TokenType::OpenBracket => depth += 1,
+            TokenType::CloseBrace | TokenType::CloseParen | TokenType::CloseBracket => depth -= 1,
+            _ => {}
+        }
+    }
+    match tokens.last() {
+        Some(last) => {
+            depth > 0
+                || !matches!(
+                    last.token_type,
+                    TokenType::Semicolon | TokenType::CloseBrace
+                )
+        }
+        None => false,
+    }
+}
+
+fn report(errors: &[FunError], source: &str) {
+    for error in errors {
+        eprintln!("{}", error.render(source));
+    }
+}
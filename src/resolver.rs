@@ -0,0 +1,373 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use super::ast::*;
+use super::builtins::{BUILTINS, MODULES};
+use super::error::{FunError, SyntaxError};
+use super::span::Span;
+
+/// Works out, before anything runs, which declaration every variable refers
+/// to and stores how many scopes up it lives in the AST.
+///
+/// Top-level code runs in order, so reading or assigning a name that has not
+/// been declared yet is reported here. Inside functions, unknown names are
+/// left to the interpreter as globals, since they may be declared after the
+/// function but before it is called.
+#[derive(Clone)]
+pub struct Resolver {
+    globals: HashSet<String>,
+    /// The enum each global variant belongs to, since variants share the
+    /// global namespace.
+    variants: HashMap<String, String>,
+    /// One set per open local scope, innermost last.
+    scopes: Vec<HashSet<String>>,
+    function_depth: usize,
+    errors: Vec<FunError>,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            globals: BUILTINS
+                .iter()
+                .map(|builtin| builtin.name)
+                .chain(MODULES.iter().map(|module| module.name))
+                .map(str::to_string)
+                .collect(),
+            variants: HashMap::new(),
+            scopes: Vec::new(),
+            function_depth: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Resolves `statements`, returning every error found. Globals declared
+    /// by earlier calls stay known.
+    pub fn resolve(&mut self, statements: &mut [Stmt]) -> Result<(), Vec<FunError>> {
+        self.statements(statements);
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn error(&mut self, error: SyntaxError, span: &Span) {
+        self.errors.push(FunError::new(error, span));
+    }
+
+    fn statements(&mut self, statements: &mut [Stmt]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn scoped(&mut self, names: impl IntoIterator<Item = String>, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(names.into_iter().collect());
+        f(self);
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &str, span: &Span) {
+        match self.scopes.last_mut() {
+            Some(scope) => {
+                if !scope.insert(name.to_string()) {
+                    self.error(SyntaxError::AlreadyDeclared(name.to_string()), span);
+                }
+            }
+            None => {
+                self.globals.insert(name.to_string());
+            }
+        }
+    }
+
+    fn depth_of(&mut self, name: &str, assignment: bool, span: &Span) -> Option<usize> {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains(name) {
+                return Some(depth);
+            }
+        }
+
+        if self.function_depth == 0 && !self.globals.contains(name) {
+            let error = if assignment {
+                SyntaxError::UndeclaredAssignment(name.to_string())
+            } else {
+                SyntaxError::UndeclaredVariable(name.to_string())
+            };
+            self.error(error, span);
+        }
+        None
+    }
+
+    fn statement(&mut self, statement: &mut Stmt) {
+        let span = &statement.span;
+        match &mut statement.kind {
+            StmtKind::Let { name, value, .. } => {
+                // The value is resolved first, so `let x = x + 1;` reads the
+                // `x` of an enclosing scope
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+                self.declare(name, span);
+            }
+            StmtKind::Assign { name, value, depth } => {
+                self.expression(value);
+                *depth = self.depth_of(name, true, span);
+            }
+            StmtKind::SetIndex {
+                target,
+                index,
+                value,
+            } => {
+                self.expression(target);
+                self.expression(index);
+                self.expression(value);
+            }
+            StmtKind::SetField { target, value, .. } => {
+                self.expression(target);
+                self.expression(value);
+            }
+            StmtKind::Print(expr) | StmtKind::Expression(expr) => {
+                self.expression(expr);
+            }
+            StmtKind::Struct(struct_) => self.declare(&struct_.name, &struct_.span),
+            StmtKind::Enum(enum_) => {
+                self.declare(&enum_.name, &enum_.span);
+                if self.scopes.is_empty() {
+                    // Redeclaring an enum replaces its variants
+                    self.variants.retain(|_, owner| *owner != enum_.name);
+                }
+                for variant in &enum_.variants {
+                    if self.scopes.is_empty() {
+                        if let Some(owner) = self.variants.get(&variant.name) {
+                            let error = SyntaxError::VariantAlreadyDeclared {
+                                variant: variant.name.clone(),
+                                enum_: owner.clone(),
+                            };
+                            self.error(error, &variant.span);
+                            continue;
+                        }
+                        self.variants
+                            .insert(variant.name.clone(), enum_.name.clone());
+                    }
+                    self.declare(&variant.name, &variant.span);
+                }
+            }
+            StmtKind::Function(function) => {
+                let name = function.name.clone().expect("declared functions are named");
+                self.declare(&name, &function.span);
+                self.function(function);
+            }
+            StmtKind::Return(value) => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            StmtKind::Block(statements) => {
+                self.scoped([], |resolver| resolver.statements(statements));
+            }
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition);
+                self.scoped([], |resolver| resolver.statements(then_branch));
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            StmtKind::While { condition, body } => {
+                self.expression(condition);
+                self.scoped([], |resolver| resolver.statements(body));
+            }
+            StmtKind::For {
+                key,
+                variable,
+                iterable,
+                body,
+            } => {
+                match iterable {
+                    Iterable::Range {
+                        start, end, step, ..
+                    } => {
+                        self.expression(start);
+                        self.expression(end);
+                        if let Some(step) = step {
+                            self.expression(step);
+                        }
+                    }
+                    Iterable::Expr(expr) => self.expression(expr),
+                }
+                let variables = key.iter().chain([&*variable]).cloned();
+                self.scoped(variables, |resolver| resolver.statements(body));
+            }
+            StmtKind::Match { value, arms } => {
+                self.expression(value);
+                for arm in arms {
+                    let mut seen = HashSet::new();
+                    for binding in arm.bindings() {
+                        if !seen.insert(binding) {
+                            self.error(SyntaxError::DuplicateBinding(binding.clone()), &arm.span);
+                        }
+                    }
+                    let bindings: Vec<String> = arm.bindings().cloned().collect();
+                    self.scoped(bindings, |resolver| {
+                        if let Some(guard) = &mut arm.guard {
+                            resolver.expression(guard);
+                        }
+                        resolver.statements(&mut arm.body)
+                    });
+                }
+            }
+            StmtKind::Break | StmtKind::Continue => {}
+        }
+    }
+
+    fn function(&mut self, function: &mut Rc<Function>) {
+        let function = Rc::make_mut(function);
+        let params = function.params.iter().map(|param| param.name.clone());
+        let mut seen = HashSet::new();
+        for param in &function.params {
+            if !seen.insert(&param.name) {
+                self.error(
+                    SyntaxError::DuplicateParameter(param.name.clone()),
+                    &param.span,
+                );
+            }
+        }
+
+        self.function_depth += 1;
+        self.scoped(params.collect::<Vec<_>>(), |resolver| {
+            resolver.statements(&mut function.body)
+        });
+        self.function_depth -= 1;
+    }
+
+    fn expression(&mut self, expr: &mut Expr) {
+        let span = &expr.span;
+        match &mut expr.kind {
+            ExprKind::Int(_)
+            | ExprKind::Float(_)
+            | ExprKind::String(_)
+            | ExprKind::Bool(_)
+            | ExprKind::None => {}
+            ExprKind::Variable { name, depth } => {
+                *depth = self.depth_of(name, false, span);
+            }
+            ExprKind::Unary { expr, .. } => self.expression(expr),
+            ExprKind::Binary { left, right, .. } | ExprKind::Logical { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            ExprKind::Call { callee, args } => {
+                self.expression(callee);
+                for arg in args {
+                    self.expression(arg);
+                }
+            }
+            ExprKind::Function(function) => self.function(function),
+            ExprKind::Struct {
+                name,
+                depth,
+                fields,
+            } => {
+                for field in fields {
+                    self.expression(&mut field.value);
+                }
+                *depth = self.depth_of(name, false, span);
+            }
+            ExprKind::Field { target, .. } => self.expression(target),
+            ExprKind::List(elements) | ExprKind::Interpolation(elements) => {
+                for element in elements {
+                    self.expression(element);
+                }
+            }
+            ExprKind::Map(entries) => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            }
+            ExprKind::Index { target, index } => {
+                self.expression(target);
+                self.expression(index);
+            }
+            ExprKind::Slice {
+                target, start, end, ..
+            } => {
+                self.expression(target);
+                for bound in [start, end].into_iter().flatten() {
+                    self.expression(bound);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{ErrorKind, SyntaxError};
+
+    /// The kinds of the errors found in `source`, in the order found.
+    fn errors(source: &str) -> Vec<ErrorKind> {
+        match crate::check("test.fun", source) {
+            Ok(_) => Vec::new(),
+            Err(errors) => errors.iter().map(|error| error.kind().clone()).collect(),
+        }
+    }
+
+    #[test]
+    fn reports_every_error() {
+        let source = "
+            print a;
+            b = 1;
+            fn f(x, x) {
+                let y = 1;
+                let y = 2;
+            }
+            print c;
+        ";
+        let expected: [ErrorKind; 5] = [
+            SyntaxError::UndeclaredVariable("a".to_string()).into(),
+            SyntaxError::UndeclaredAssignment("b".to_string()).into(),
+            SyntaxError::DuplicateParameter("x".to_string()).into(),
+            SyntaxError::AlreadyDeclared("y".to_string()).into(),
+            SyntaxError::UndeclaredVariable("c".to_string()).into(),
+        ];
+        assert_eq!(errors(source), expected);
+    }
+
+    #[test]
+    fn variant_declared_by_another_enum() {
+        let source = "
+            enum A { X, Y }
+            enum B { X, Z }
+            enum A { X, W }
+        ";
+        let expected: [ErrorKind; 1] = [SyntaxError::VariantAlreadyDeclared {
+            variant: "X".to_string(),
+            enum_: "A".to_string(),
+        }
+        .into()];
+        assert_eq!(errors(source), expected);
+    }
+
+    #[test]
+    fn globals_can_be_used_in_functions_before_they_are_declared() {
+        let source = "
+            fn f() {
+                return later;
+            }
+            let later = 1;
+            print f();
+        ";
+        assert_eq!(errors(source), []);
+    }
+}
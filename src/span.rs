@@ -0,0 +1,32 @@
+use std::fmt;
+use std::sync::Arc;
+
+/// A range of source text: the file it is in, where it starts as a 1-based
+/// line and column, and its byte range in the file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub file: Arc<str>,
+    pub line: usize,
+    pub column: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// A span covering both `self` and `other`, which must come after it.
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            file: Arc::clone(&self.file),
+            line: self.line,
+            column: self.column,
+            start: self.start,
+            end: other.end.max(self.end),
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
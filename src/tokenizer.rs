@@ -1,4 +1,8 @@
-use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+use super::error::{FunError, LexicalError};
+use super::parser::MAX_NESTING;
+use super::span::Span;
 
 macro_rules! keyword_case {
     ($tokenizer:ident, $keyword:literal, $keyword_type:expr) => {
@@ -7,15 +11,15 @@ macro_rules! keyword_case {
             TokenType::Keyword($keyword_type),
             $keyword.to_string()
         );
-        $tokenizer.consume_times($keyword.len());
     };
 }
 
 macro_rules! add_token {
     ($tokenizer:ident, $token_type:expr, $token_value:expr) => {
+        let span = $tokenizer.token_span();
         $tokenizer
             .tokens
-            .push(Token::new($token_type, $token_value));
+            .push(Token::new($token_type, $token_value, span));
     };
 }
 
@@ -23,6 +27,24 @@ macro_rules! add_token {
 pub enum KeywordType {
     Let,
     None,
+    True,
+    False,
+    And,
+    Or,
+    Not,
+    If,
+    Else,
+    While,
+    Break,
+    Continue,
+    For,
+    In,
+    Step,
+    Fn,
+    Return,
+    Struct,
+    Enum,
+    Match,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -34,186 +56,914 @@ pub enum LiteralType {
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
     Assignment,
+    FatArrow,
     Ident,
     Semicolon,
     OpenParen,
     CloseParen,
+    OpenBrace,
+    CloseBrace,
+    OpenBracket,
+    CloseBracket,
     Colon,
+    Question,
+    Comma,
+    Dot,
+    DotDot,
+    DotDotEqual,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    EqualEqual,
+    BangEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Bang,
+    AndAnd,
+    OrOr,
     Type,
     Keyword(KeywordType),
     Literal(LiteralType),
+    /// Text of an interpolated string up to a `{` that starts an embedded
+    /// expression, whose tokens follow, ended by `InterpolationEnd`.
+    StringPart,
+    /// The `}` ending an expression embedded in a string.
+    InterpolationEnd,
+    /// Text of an interpolated string after its last embedded expression.
+    StringEnd,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub token_type: TokenType,
     pub value: String,
+    pub span: Span,
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, value: String) -> Self {
-        Self { token_type, value }
+    pub fn new(token_type: TokenType, value: String, span: Span) -> Self {
+        Self {
+            token_type,
+            value,
+            span,
+        }
+    }
+}
+
+/// A char of a string literal. Escaped chars are never line breaks or
+/// indentation.
+#[derive(Debug, Clone, Copy)]
+struct StringChar {
+    char: char,
+    escaped: bool,
+}
+
+impl StringChar {
+    fn is_line_break(&self) -> bool {
+        self.char == '\n' && !self.escaped
+    }
+
+    fn is_indentation(&self) -> bool {
+        (self.char == ' ' || self.char == '\t') && !self.escaped
+    }
+}
+
+/// Removes, from the parts of a triple-quoted string, the line break right
+/// after the opening quotes, the last line if it only indents the closing
+/// quotes, and the indentation every line shares, counting that of the
+/// closing quotes and leaving out blank lines.
+fn strip_indentation(texts: &mut [Vec<StringChar>]) {
+    let first = &mut texts[0];
+    if let Some(end) = first.iter().position(|char| !char.is_indentation()) {
+        if first[end].is_line_break() {
+            first.drain(..=end);
+        }
+    }
+
+    let mut shared: Option<usize> = None;
+    let last = texts.last_mut().expect("a string has at least one part");
+    if let Some(line_break) = last.iter().rposition(StringChar::is_line_break) {
+        if last[line_break + 1..]
+            .iter()
+            .all(StringChar::is_indentation)
+        {
+            shared = Some(last.len() - line_break - 1);
+            last.truncate(line_break);
+        }
+    }
+
+    let count = texts.len();
+    for (i, text) in texts.iter().enumerate() {
+        // The indentation of the current line while only indentation was seen
+        let mut indentation = (i == 0).then_some(0);
+        for char in text {
+            if char.is_line_break() {
+                indentation = Some(0);
+            } else if let Some(width) = indentation {
+                if char.is_indentation() {
+                    indentation = Some(width + 1);
+                } else {
+                    shared = Some(shared.map_or(width, |shared| shared.min(width)));
+                    indentation = None;
+                }
+            }
+        }
+        // A line that goes on with an embedded expression is not blank
+        if let (Some(width), true) = (indentation, i + 1 < count) {
+            shared = Some(shared.map_or(width, |shared| shared.min(width)));
+        }
+    }
+
+    let shared = shared.unwrap_or(0);
+    for (i, text) in texts.iter_mut().enumerate() {
+        let mut removed = (i == 0).then_some(0);
+        text.retain(|char| {
+            if char.is_line_break() {
+                removed = Some(0);
+            } else if let Some(count) = removed {
+                if char.is_indentation() && count < shared {
+                    removed = Some(count + 1);
+                    return false;
+                }
+                removed = None;
+            }
+            true
+        });
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+struct Position {
+    byte: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Position {
+    const START: Position = Position {
+        byte: 0,
+        line: 1,
+        column: 1,
+    };
+}
+
 pub struct Tokenizer {
-    text: String,
+    file: Arc<str>,
+    text: Vec<char>,
     index: usize,
+    position: Position,
+    /// Where the token being scanned starts.
+    token_start: Position,
     tokens: Vec<Token>,
+    /// How many embedded expressions enclose the token being scanned.
+    interpolations: usize,
 }
 
 impl Tokenizer {
-    pub fn new(text: String) -> Self {
+    pub fn new(file: &str, text: String) -> Self {
         Self {
-            text,
+            file: Arc::from(file),
+            text: text.chars().collect(),
             index: 0,
+            position: Position::START,
+            token_start: Position::START,
             tokens: Vec::new(),
+            interpolations: 0,
         }
     }
 
     fn consume(&mut self) -> char {
-        let cur = self.text.chars().nth(self.index).unwrap();
+        let cur = self.text[self.index];
         self.index += 1;
+        self.position.byte += cur.len_utf8();
+        if cur == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
+        }
         cur
     }
 
-    fn consume_times(&mut self, times: usize) -> () {
+    fn span_between(&self, start: Position, end: Position) -> Span {
+        Span {
+            file: Arc::clone(&self.file),
+            line: start.line,
+            column: start.column,
+            start: start.byte,
+            end: end.byte,
+        }
+    }
+
+    /// The span from the start of the current token up to the current char.
+    fn token_span(&self) -> Span {
+        self.span_between(self.token_start, self.position)
+    }
+
+    /// The span of the char that is about to be consumed.
+    fn char_span(&self) -> Span {
+        let mut end = self.position;
+        end.byte += self.peek(0).map_or(0, char::len_utf8);
+        self.span_between(self.position, end)
+    }
+
+    fn consume_times(&mut self, times: usize) {
         for _ in 0..times {
             self.consume();
         }
     }
 
     fn peek(&self, offset: usize) -> Option<char> {
-        if self.index + offset >= self.text.len() {
-            None
+        self.text.get(self.index + offset).copied()
+    }
+
+    fn create_literal(&mut self, literal_type: LiteralType) -> Result<Token, FunError> {
+        let Some(char) = self.peek(0) else {
+            return Err(FunError::new(
+                LexicalError::UnterminatedString,
+                &self.token_span(),
+            ));
+        };
+        match literal_type {
+            LiteralType::String => self.string(false),
+            LiteralType::Number => self.number(char),
+        }
+    }
+
+    /// Scans a number literal starting with `char`. Its value is written
+    /// without separators, in decimal, with a `.` or exponent only if the
+    /// literal had one, which makes it a float.
+    fn number(&mut self, char: char) -> Result<Token, FunError> {
+        let radix = match (char, self.peek(1)) {
+            ('0', Some('x' | 'X')) => Some(16),
+            ('0', Some('o' | 'O')) => Some(8),
+            ('0', Some('b' | 'B')) => Some(2),
+            _ => None,
+        };
+        if let Some(radix) = radix {
+            let prefix: String = [char, self.peek(1).unwrap_or_default()].iter().collect();
+            self.consume_times(2);
+            let digits = self.digits(radix)?;
+            if digits.is_empty() {
+                return Err(FunError::new(
+                    LexicalError::MissingDigits(prefix),
+                    &self.token_span(),
+                ));
+            }
+            self.end_of_number(radix)?;
+            return match i64::from_str_radix(&digits, radix) {
+                Ok(value) => Ok(Token::new(
+                    TokenType::Literal(LiteralType::Number),
+                    value.to_string(),
+                    self.token_span(),
+                )),
+                Err(_) => Err(FunError::new(
+                    LexicalError::NumberTooLarge,
+                    &self.token_span(),
+                )),
+            };
+        }
+
+        let mut builder = self.digits(10)?;
+        if self.peek(0) == Some('.') && self.peek(1) != Some('.') {
+            // Not the start of a range such as `0..10`
+            self.consume();
+            if builder.is_empty() {
+                builder.push('0');
+            }
+            builder.push('.');
+            builder.push_str(&self.digits(10)?);
+            if self.peek(0) == Some('.') && self.peek(1) != Some('.') {
+                return Err(FunError::new(
+                    LexicalError::MultipleDecimalPoints,
+                    &self.char_span(),
+                ));
+            }
+        }
+        if let Some(exponent @ ('e' | 'E')) = self.peek(0) {
+            self.consume();
+            builder.push(exponent);
+            if let Some(sign @ ('+' | '-')) = self.peek(0) {
+                self.consume();
+                builder.push(sign);
+            }
+            let digits = self.digits(10)?;
+            if digits.is_empty() {
+                return Err(FunError::new(
+                    LexicalError::MissingExponent,
+                    &self.token_span(),
+                ));
+            }
+            builder.push_str(&digits);
+        }
+        self.end_of_number(10)?;
+
+        let fits = if builder.contains(['.', 'e', 'E']) {
+            builder.parse::<f64>().is_ok_and(f64::is_finite)
         } else {
-            Some(self.text.chars().nth(self.index + offset)?)
+            builder.parse::<i64>().is_ok()
+        };
+        if !fits {
+            return Err(FunError::new(
+                LexicalError::NumberTooLarge,
+                &self.token_span(),
+            ));
         }
+        Ok(Token::new(
+            TokenType::Literal(LiteralType::Number),
+            builder,
+            self.token_span(),
+        ))
     }
 
-    fn spells_out(&mut self, keyword: &str) -> bool {
-        for (i, c) in keyword.chars().enumerate() {
-            if Some(c) != self.peek(i) {
-                return false;
+    /// Scans digits in `radix`, which may be split by `_` separators, and
+    /// returns them without the separators. Letters in larger bases are
+    /// reported as invalid digits.
+    fn digits(&mut self, radix: u32) -> Result<String, FunError> {
+        let mut digits = String::new();
+        while let Some(char) = self.peek(0) {
+            if char == '_' {
+                let next_is_digit = self.peek(1).is_some_and(|next| next.is_digit(radix));
+                if digits.is_empty() || !next_is_digit {
+                    return Err(FunError::new(
+                        LexicalError::MisplacedSeparator,
+                        &self.char_span(),
+                    ));
+                }
+            } else if char.is_digit(radix) {
+                digits.push(char);
+            } else if radix != 10 && char.is_ascii_alphanumeric() {
+                return Err(FunError::new(
+                    LexicalError::InvalidDigit(char, radix),
+                    &self.char_span(),
+                ));
+            } else {
+                break;
             }
+            self.consume();
         }
-        true
+        Ok(digits)
     }
 
-    fn create_literal(&mut self, literal_type: LiteralType) -> std::io::Result<Token> {
-        let Some(mut char) = self.peek(0) else {
-            return Err(Error::new(ErrorKind::Other, "No char"));
+    /// Reports a letter or digit right after a number, such as the `p` in
+    /// `12px`, or a decimal point after a number in another base.
+    fn end_of_number(&self, radix: u32) -> Result<(), FunError> {
+        let fraction = radix != 10
+            && self.peek(0) == Some('.')
+            && self.peek(1).is_some_and(|char| char.is_ascii_digit());
+        match self.peek(0) {
+            Some(char) if char.is_alphanumeric() || char == '_' || fraction => Err(FunError::new(
+                LexicalError::InvalidDigit(char, radix),
+                &self.char_span(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Scans a string literal after its opening quote. Its text has escapes
+    /// replaced, unless it is `raw`, and if it starts with three quotes, the
+    /// indentation of its lines removed. The parts before embedded
+    /// expressions are pushed as tokens, and the rest is returned.
+    fn string(&mut self, raw: bool) -> Result<Token, FunError> {
+        // Embedded expressions are scanned as tokens of their own, so where
+        // the string and each of its parts start is kept here
+        let start = self.token_start;
+        let triple = self.peek(0) == Some('"') && self.peek(1) == Some('"');
+        if triple {
+            self.consume_times(2);
+        }
+
+        let mut texts: Vec<Vec<StringChar>> = vec![Vec::new()];
+        // The indexes in `tokens` of the parts before embedded expressions
+        let mut parts = Vec::new();
+        let mut part_start = start;
+        loop {
+            let text = texts.last_mut().expect("a string has at least one part");
+            match self.peek(0) {
+                Some('"') if !triple => break,
+                Some('"') if self.peek(1) == Some('"') && self.peek(2) == Some('"') => {
+                    self.consume_times(2);
+                    break;
+                }
+                Some('\\') if !raw => text.push(self.escape()?),
+                Some(brace @ ('{' | '}')) if !raw && self.peek(1) == Some(brace) => {
+                    text.push(StringChar {
+                        char: brace,
+                        escaped: true,
+                    });
+                    self.consume_times(2);
+                }
+                Some('{') if !raw => {
+                    self.consume();
+                    parts.push(self.tokens.len());
+                    self.tokens.push(Token::new(
+                        TokenType::StringPart,
+                        String::new(),
+                        self.span_between(part_start, self.position),
+                    ));
+                    self.interpolation(start)?;
+                    part_start = self.position;
+                    texts.push(Vec::new());
+                }
+                Some('}') if !raw => {
+                    return Err(FunError::new(
+                        LexicalError::UnmatchedBrace,
+                        &self.char_span(),
+                    ));
+                }
+                Some(char) => {
+                    text.push(StringChar {
+                        char,
+                        escaped: false,
+                    });
+                    self.consume();
+                }
+                None => {
+                    return Err(FunError::new(
+                        LexicalError::UnterminatedString,
+                        &self.span_between(start, self.position),
+                    ));
+                }
+            }
+        }
+        self.consume();
+
+        if triple {
+            strip_indentation(&mut texts);
+        }
+        let mut texts = texts
+            .into_iter()
+            .map(|text| text.into_iter().map(|char| char.char).collect::<String>());
+        for &index in &parts {
+            self.tokens[index].value = texts.next().expect("every part has a text");
+        }
+        let token_type = if parts.is_empty() {
+            TokenType::Literal(LiteralType::String)
+        } else {
+            TokenType::StringEnd
         };
-        let mut builder = String::new();
-        match literal_type {
-            LiteralType::String => {
-                builder.push(char);
+        Ok(Token::new(
+            token_type,
+            texts.next().expect("the last part has a text"),
+            self.span_between(part_start, self.position),
+        ))
+    }
+
+    /// Scans an escape sequence, starting at its backslash.
+    fn escape(&mut self) -> Result<StringChar, FunError> {
+        let start = self.position;
+        self.consume();
+        let char = match self.peek(0) {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('"') => '"',
+            Some('u') => return self.unicode_escape(start),
+            Some(char) => {
                 self.consume();
-                char = self.peek(0).unwrap();
-                while char != '"' {
-                    builder.push(char);
+                return Err(FunError::new(
+                    LexicalError::InvalidEscape(format!("\\{}", char.escape_debug())),
+                    &self.span_between(start, self.position),
+                ));
+            }
+            None => {
+                return Err(FunError::new(
+                    LexicalError::UnterminatedString,
+                    &self.span_between(start, self.position),
+                ));
+            }
+        };
+        self.consume();
+        Ok(StringChar {
+            char,
+            escaped: true,
+        })
+    }
+
+    /// Scans the rest of a `\u{...}` escape, whose backslash is at `start`.
+    fn unicode_escape(&mut self, start: Position) -> Result<StringChar, FunError> {
+        self.consume();
+        let mut written = String::from("\\u");
+        let mut digits = String::new();
+        let mut closed = false;
+        if self.peek(0) == Some('{') {
+            written.push('{');
+            self.consume();
+            while let Some(char) = self.peek(0) {
+                if char == '}' {
+                    written.push(char);
                     self.consume();
-                    let Some(char_) = self.peek(0) else {
-                        return Err(Error::new(ErrorKind::Other, "Unterminated string literal"));
-                    };
-                    char = char_;
+                    closed = true;
+                    break;
                 }
+                if !char.is_ascii_hexdigit() {
+                    break;
+                }
+                written.push(char);
+                digits.push(char);
                 self.consume();
-                Ok(Token::new(TokenType::Literal(LiteralType::String), builder))
             }
-            LiteralType::Number => {
-                if char == '.' {
-                    builder.push('0');
-                } else {
-                    builder.push(char);
+        }
+
+        let char = u32::from_str_radix(&digits, 16)
+            .ok()
+            .filter(|_| closed && digits.len() <= 6)
+            .and_then(char::from_u32);
+        match char {
+            Some(char) => Ok(StringChar {
+                char,
+                escaped: true,
+            }),
+            None => Err(FunError::new(
+                LexicalError::InvalidUnicodeEscape(written),
+                &self.span_between(start, self.position),
+            )),
+        }
+    }
+
+    /// Scans the tokens of an expression embedded in the string starting at
+    /// `start`, up to and including the `}` that ends it.
+    fn interpolation(&mut self, start: Position) -> Result<(), FunError> {
+        if self.interpolations == MAX_NESTING {
+            return Err(FunError::new(
+                LexicalError::TooDeeplyNested,
+                &self.span_between(start, self.position),
+            ));
+        }
+        self.interpolations += 1;
+        let result = self.interpolation_inner(start);
+        self.interpolations -= 1;
+        result
+    }
+
+    fn interpolation_inner(&mut self, start: Position) -> Result<(), FunError> {
+        let mut depth = 0;
+        loop {
+            match self.peek(0) {
+                Some('}') if depth == 0 => {
+                    self.token_start = self.position;
+                    self.consume();
+                    add_token!(self, TokenType::InterpolationEnd, "}".to_string());
+                    return Ok(());
                 }
-                self.consume();
-                char = self.peek(0).unwrap();
-                while char.is_ascii_digit() || char == '.' {
-                    if char == '.' && builder.contains('.') {
-                        return Err(Error::new(
-                            ErrorKind::Other,
-                            "Multiple decimal points in number",
-                        ));
+                Some(char) => {
+                    match char {
+                        '{' => depth += 1,
+                        '}' => depth -= 1,
+                        _ => {}
                     }
-                    builder.push(char);
-                    self.consume();
-                    char = self.peek(0).unwrap();
+                    self.scan_token(char)?;
+                }
+                None => {
+                    return Err(FunError::new(
+                        LexicalError::UnterminatedString,
+                        &self.span_between(start, self.position),
+                    ));
                 }
-                Ok(Token::new(TokenType::Literal(LiteralType::Number), builder))
             }
         }
     }
 
-    pub fn tokenize(&mut self) -> std::io::Result<Vec<Token>> {
+    pub fn tokenize(&mut self) -> Result<Vec<Token>, FunError> {
         self.tokens = Vec::new();
-        while self.peek(0) != None {
-            let mut char = self.peek(0).unwrap();
-            if char.is_whitespace() {
+        while let Some(char) = self.peek(0) {
+            self.scan_token(char)?;
+        }
+
+        self.index = 0;
+        self.position = Position::START;
+        Ok(self.tokens.to_vec())
+    }
+
+    /// Scans the token starting with `char`, or skips whitespace or a comment.
+    fn scan_token(&mut self, char: char) -> Result<(), FunError> {
+        self.token_start = self.position;
+        if char.is_whitespace() {
+            self.consume();
+            return Ok(());
+        } else if char == '#' {
+            while self.peek(0).is_some_and(|char| char != '\n') {
                 self.consume();
-                continue;
-            } else if char == '#' {
-                while char != '\n' {
-                    self.consume();
-                    char = self.peek(0).unwrap();
-                }
-                continue;
-            } else if self.spells_out("let") {
-                keyword_case!(self, "let", KeywordType::Let);
-            } else if self.spells_out("none") {
-                keyword_case!(self, "none", KeywordType::None);
-            } else if char.is_ascii_alphabetic() {
-                let mut builder = String::new();
+            }
+            return Ok(());
+        } else if char == 'r' && self.peek(1) == Some('"') {
+            self.consume_times(2);
+            let res = self.string(true)?;
+            self.tokens.push(res);
+        } else if char.is_ascii_alphabetic() || char == '_' {
+            let mut builder = String::new();
+            while let Some(char) = self.peek(0) {
+                if !char.is_ascii_alphanumeric() && char != '_' {
+                    break;
+                }
                 builder.push(char);
                 self.consume();
-                char = self.peek(0).unwrap();
-                while char.is_ascii_alphanumeric() {
-                    builder.push(char);
-                    self.consume();
-                    char = self.peek(0).unwrap();
+            }
+            match builder.as_str() {
+                "let" => {
+                    keyword_case!(self, "let", KeywordType::Let);
                 }
-                match builder.as_str() {
-                    "string" => {
-                        add_token!(self, TokenType::Type, "string".to_string());
-                    }
-                    "number" => {
-                        add_token!(self, TokenType::Type, "number".to_string());
-                    }
-                    _ => {
-                        add_token!(self, TokenType::Ident, builder);
-                    }
+                "if" => {
+                    keyword_case!(self, "if", KeywordType::If);
+                }
+                "else" => {
+                    keyword_case!(self, "else", KeywordType::Else);
+                }
+                "while" => {
+                    keyword_case!(self, "while", KeywordType::While);
+                }
+                "break" => {
+                    keyword_case!(self, "break", KeywordType::Break);
+                }
+                "continue" => {
+                    keyword_case!(self, "continue", KeywordType::Continue);
+                }
+                "for" => {
+                    keyword_case!(self, "for", KeywordType::For);
+                }
+                "in" => {
+                    keyword_case!(self, "in", KeywordType::In);
+                }
+                "step" => {
+                    keyword_case!(self, "step", KeywordType::Step);
+                }
+                "fn" => {
+                    keyword_case!(self, "fn", KeywordType::Fn);
+                }
+                "return" => {
+                    keyword_case!(self, "return", KeywordType::Return);
+                }
+                "struct" => {
+                    keyword_case!(self, "struct", KeywordType::Struct);
+                }
+                "enum" => {
+                    keyword_case!(self, "enum", KeywordType::Enum);
+                }
+                "match" => {
+                    keyword_case!(self, "match", KeywordType::Match);
+                }
+                "none" => {
+                    keyword_case!(self, "none", KeywordType::None);
+                }
+                "and" => {
+                    keyword_case!(self, "and", KeywordType::And);
+                }
+                "or" => {
+                    keyword_case!(self, "or", KeywordType::Or);
+                }
+                "not" => {
+                    keyword_case!(self, "not", KeywordType::Not);
+                }
+                "true" => {
+                    keyword_case!(self, "true", KeywordType::True);
+                }
+                "false" => {
+                    keyword_case!(self, "false", KeywordType::False);
+                }
+                "string" | "int" | "float" | "number" | "bool" | "list" | "map" => {
+                    add_token!(self, TokenType::Type, builder);
+                }
+                _ => {
+                    add_token!(self, TokenType::Ident, builder);
                 }
-            } else if char == '=' {
-                add_token!(self, TokenType::Assignment, "=".to_string());
-                self.consume();
-            } else if char == '"' {
-                self.consume();
-                let res = self.create_literal(LiteralType::String)?;
-                self.tokens.push(res);
-            } else if char.is_ascii_digit() || char == '.' {
-                let res = self.create_literal(LiteralType::Number)?;
-                self.tokens.push(res);
-            } else if char == ';' {
-                add_token!(self, TokenType::Semicolon, ";".to_string());
-                self.consume();
-            } else if char == ':' {
-                add_token!(self, TokenType::Colon, ":".to_string());
-                self.consume();
-            } else if char == '(' {
-                add_token!(self, TokenType::OpenParen, "(".to_string());
-                self.consume();
-            } else if char == ')' {
-                add_token!(self, TokenType::CloseParen, ")".to_string());
-                self.consume();
-            } else {
-                return Err(Error::new(
-                    ErrorKind::InvalidInput,
-                    format!("Unexpected character: {:?}", char),
-                ));
             }
+        } else if char == '=' && self.peek(1) == Some('=') {
+            self.consume_times(2);
+            add_token!(self, TokenType::EqualEqual, "==".to_string());
+        } else if char == '=' && self.peek(1) == Some('>') {
+            self.consume_times(2);
+            add_token!(self, TokenType::FatArrow, "=>".to_string());
+        } else if char == '=' {
+            self.consume();
+            add_token!(self, TokenType::Assignment, "=".to_string());
+        } else if char == '!' && self.peek(1) == Some('=') {
+            self.consume_times(2);
+            add_token!(self, TokenType::BangEqual, "!=".to_string());
+        } else if char == '!' {
+            self.consume();
+            add_token!(self, TokenType::Bang, "!".to_string());
+        } else if char == '<' && self.peek(1) == Some('=') {
+            self.consume_times(2);
+            add_token!(self, TokenType::LessEqual, "<=".to_string());
+        } else if char == '<' {
+            self.consume();
+            add_token!(self, TokenType::Less, "<".to_string());
+        } else if char == '>' && self.peek(1) == Some('=') {
+            self.consume_times(2);
+            add_token!(self, TokenType::GreaterEqual, ">=".to_string());
+        } else if char == '>' {
+            self.consume();
+            add_token!(self, TokenType::Greater, ">".to_string());
+        } else if char == '&' && self.peek(1) == Some('&') {
+            self.consume_times(2);
+            add_token!(self, TokenType::AndAnd, "&&".to_string());
+        } else if char == '|' && self.peek(1) == Some('|') {
+            self.consume_times(2);
+            add_token!(self, TokenType::OrOr, "||".to_string());
+        } else if char == '"' {
+            self.consume();
+            let res = self.create_literal(LiteralType::String)?;
+            self.tokens.push(res);
+        } else if char == '.' && self.peek(1) == Some('.') && self.peek(2) == Some('=') {
+            self.consume_times(3);
+            add_token!(self, TokenType::DotDotEqual, "..=".to_string());
+        } else if char == '.' && self.peek(1) == Some('.') {
+            self.consume_times(2);
+            add_token!(self, TokenType::DotDot, "..".to_string());
+        } else if char.is_ascii_digit()
+            || (char == '.' && self.peek(1).is_some_and(|char| char.is_ascii_digit()))
+        {
+            let res = self.create_literal(LiteralType::Number)?;
+            self.tokens.push(res);
+        } else if char == '.' {
+            self.consume();
+            add_token!(self, TokenType::Dot, ".".to_string());
+        } else if char == ';' {
+            self.consume();
+            add_token!(self, TokenType::Semicolon, ";".to_string());
+        } else if char == ',' {
+            self.consume();
+            add_token!(self, TokenType::Comma, ",".to_string());
+        } else if char == ':' {
+            self.consume();
+            add_token!(self, TokenType::Colon, ":".to_string());
+        } else if char == '?' {
+            self.consume();
+            add_token!(self, TokenType::Question, "?".to_string());
+        } else if char == '+' {
+            self.consume();
+            add_token!(self, TokenType::Plus, "+".to_string());
+        } else if char == '-' {
+            self.consume();
+            add_token!(self, TokenType::Minus, "-".to_string());
+        } else if char == '*' {
+            self.consume();
+            add_token!(self, TokenType::Star, "*".to_string());
+        } else if char == '/' {
+            self.consume();
+            add_token!(self, TokenType::Slash, "/".to_string());
+        } else if char == '%' {
+            self.consume();
+            add_token!(self, TokenType::Percent, "%".to_string());
+        } else if char == '(' {
+            self.consume();
+            add_token!(self, TokenType::OpenParen, "(".to_string());
+        } else if char == ')' {
+            self.consume();
+            add_token!(self, TokenType::CloseParen, ")".to_string());
+        } else if char == '{' {
+            self.consume();
+            add_token!(self, TokenType::OpenBrace, "{".to_string());
+        } else if char == '}' {
+            self.consume();
+            add_token!(self, TokenType::CloseBrace, "}".to_string());
+        } else if char == '[' {
+            self.consume();
+            add_token!(self, TokenType::OpenBracket, "[".to_string());
+        } else if char == ']' {
+            self.consume();
+            add_token!(self, TokenType::CloseBracket, "]".to_string());
+        } else {
+            return Err(FunError::new(
+                LexicalError::UnexpectedCharacter(char),
+                &self.char_span(),
+            ));
         }
+        Ok(())
+    }
+}
 
-        self.index = 0;
-        Ok(self.tokens.to_vec())
+#[cfg(test)]
+mod tests {
+    use super::{LiteralType, TokenType, Tokenizer};
+    use crate::error::{ErrorKind, LexicalError};
+
+    /// The type and value of every token in `source`.
+    fn tokens(source: &str) -> Vec<(TokenType, String)> {
+        Tokenizer::new("test.fun", source.to_string())
+            .tokenize()
+            .expect("source has no lexical errors")
+            .into_iter()
+            .map(|token| (token.token_type, token.value))
+            .collect()
+    }
+
+    /// The value of the single string literal in `source`.
+    fn string(source: &str) -> String {
+        match tokens(source).as_slice() {
+            [(TokenType::Literal(LiteralType::String), value)] => value.clone(),
+            tokens => panic!("expected a string literal, got {:?}", tokens),
+        }
+    }
+
+    fn error(source: &str) -> ErrorKind {
+        Tokenizer::new("test.fun", source.to_string())
+            .tokenize()
+            .expect_err("source has a lexical error")
+            .kind()
+            .clone()
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(string(r#""a\tb\n\\\"{{}}""#), "a\tb\n\\\"{}");
+        assert_eq!(string(r#""\u{48}\u{1F600}""#), "H\u{1F600}");
+        assert_eq!(string(r#"r"a\n{b}""#), "a\\n{b}");
+    }
+
+    #[test]
+    fn invalid_escapes() {
+        assert_eq!(
+            error(r#""\q""#),
+            LexicalError::InvalidEscape("\\q".to_string()).into()
+        );
+        for escape in [r"\u{}", r"\u{110000}", r"\u{D800}", r"\u{1234567}"] {
+            assert_eq!(
+                error(&format!("\"{}\"", escape)),
+                LexicalError::InvalidUnicodeEscape(escape.to_string()).into()
+            );
+        }
+        assert_eq!(
+            error(r#""\u41""#),
+            LexicalError::InvalidUnicodeEscape("\\u".to_string()).into()
+        );
+    }
+
+    #[test]
+    fn triple_quoted_strips_indentation() {
+        let source = "\"\"\"\n    a\n      b\n\n    c\n    \"\"\"";
+        assert_eq!(string(source), "a\n  b\n\nc");
+    }
+
+    #[test]
+    fn triple_quoted_counts_closing_quotes() {
+        let source = "\"\"\"\n    a\n  \"\"\"";
+        assert_eq!(string(source), "  a");
+    }
+
+    #[test]
+    fn triple_quoted_keeps_escaped_indentation() {
+        let source = "\"\"\"\n  a\\n  b\n  \"\"\"";
+        assert_eq!(string(source), "a\n  b");
+    }
+
+    #[test]
+    fn triple_quoted_with_embedded_expression() {
+        let source = "\"\"\"\n    x {1}\n    y\n    \"\"\"";
+        assert_eq!(
+            tokens(source),
+            [
+                (TokenType::StringPart, "x ".to_string()),
+                (TokenType::Literal(LiteralType::Number), "1".to_string()),
+                (TokenType::InterpolationEnd, "}".to_string()),
+                (TokenType::StringEnd, "\ny".to_string()),
+            ]
+        );
+    }
+
+    /// The value of the single number literal in `source`.
+    fn number(source: &str) -> String {
+        match tokens(source).as_slice() {
+            [(TokenType::Literal(LiteralType::Number), value)] => value.clone(),
+            tokens => panic!("expected a number literal, got {:?}", tokens),
+        }
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(number("0xFF"), "255");
+        assert_eq!(number("0o17"), "15");
+        assert_eq!(number("0b1010_1010"), "170");
+        assert_eq!(number("1_000_000"), "1000000");
+        assert_eq!(number("1.5"), "1.5");
+        assert_eq!(number("1.5e3"), "1.5e3");
+        assert_eq!(number("2E-2"), "2E-2");
+        assert_eq!(number("9223372036854775807"), "9223372036854775807");
+    }
+
+    #[test]
+    fn invalid_numbers() {
+        let cases = [
+            ("0x", LexicalError::MissingDigits("0x".to_string())),
+            ("0b12", LexicalError::InvalidDigit('2', 2)),
+            ("0o8", LexicalError::InvalidDigit('8', 8)),
+            ("0x1.5", LexicalError::InvalidDigit('.', 16)),
+            ("1e", LexicalError::MissingExponent),
+            ("1e+", LexicalError::MissingExponent),
+            ("1__0", LexicalError::MisplacedSeparator),
+            ("1_", LexicalError::MisplacedSeparator),
+            ("1.2.3", LexicalError::MultipleDecimalPoints),
+            ("9223372036854775808", LexicalError::NumberTooLarge),
+            ("0x1_0000_0000_0000_0000", LexicalError::NumberTooLarge),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(error(source), expected.into(), "{}", source);
+        }
     }
 }
@@ -0,0 +1,362 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::Hash;
+use std::rc::Rc;
+use std::thread::LocalKey;
+
+use super::ast::{Enum, Function, Struct, Type};
+use super::builtins::{Builtin, Module};
+use super::environment::Env;
+use super::map::Map;
+
+thread_local! {
+    /// The lists, maps and instances being displayed.
+    static DISPLAYING: RefCell<HashSet<*const ()>> = RefCell::new(HashSet::new());
+    /// The pairs of lists, maps and instances being compared.
+    static COMPARING: RefCell<HashSet<(*const (), *const ())>> = RefCell::new(HashSet::new());
+}
+
+/// Marks a list, map or instance as being displayed or compared for as long
+/// as it lives. Lists, maps and instances can contain themselves, which is
+/// found by visiting one again before leaving it.
+struct Visit<K: Copy + Eq + Hash + 'static> {
+    visiting: &'static LocalKey<RefCell<HashSet<K>>>,
+    key: K,
+}
+
+impl<K: Copy + Eq + Hash + 'static> Visit<K> {
+    /// Starts visiting `key`, or returns `None` if it is already being visited.
+    fn enter(visiting: &'static LocalKey<RefCell<HashSet<K>>>, key: K) -> Option<Self> {
+        visiting
+            .with_borrow_mut(|keys| keys.insert(key))
+            .then_some(Self { visiting, key })
+    }
+}
+
+impl<K: Copy + Eq + Hash + 'static> Drop for Visit<K> {
+    fn drop(&mut self) {
+        self.visiting.with_borrow_mut(|keys| keys.remove(&self.key));
+    }
+}
+
+/// A function together with the environment it was created in.
+pub struct Closure {
+    pub function: Rc<Function>,
+    pub environment: Env,
+}
+
+impl Closure {
+    pub fn name(&self) -> &str {
+        self.function.name.as_deref().unwrap_or("anonymous")
+    }
+}
+
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The environment may contain this closure, so it is left out
+        write!(f, "Closure({:?})", self.name())
+    }
+}
+
+/// A value of a struct type, with its fields in the order they are declared.
+#[derive(Debug)]
+pub struct Instance {
+    pub struct_: Rc<Struct>,
+    pub fields: Vec<Value>,
+}
+
+/// A value of an enum type: one of its variants and the values it carries.
+#[derive(Debug)]
+pub struct VariantValue {
+    pub enum_: Rc<Enum>,
+    pub index: usize,
+    pub values: Vec<Value>,
+}
+
+impl VariantValue {
+    pub fn name(&self) -> &str {
+        &self.enum_.variants[self.index].name
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    String(String),
+    Bool(bool),
+    None,
+    Function(Rc<Closure>),
+    Builtin(&'static Builtin),
+    Module(&'static Module),
+    /// Shared, so changes made through one variable are seen through all
+    /// others holding the same list.
+    List(Rc<RefCell<Vec<Value>>>),
+    /// Shared like lists.
+    Map(Rc<RefCell<Map>>),
+    /// A struct declaration, which builds instances.
+    Struct(Rc<Struct>),
+    /// Shared like lists.
+    Instance(Rc<RefCell<Instance>>),
+    /// An enum declaration.
+    Enum(Rc<Enum>),
+    /// The variant at this index of an enum, which is called with the values
+    /// the variant carries to build it.
+    Constructor(Rc<Enum>, usize),
+    Variant(Rc<VariantValue>),
+}
+
+impl Value {
+    /// The name of the value's type, which for instances and variants is the
+    /// name of their struct or enum.
+    pub fn type_name(&self) -> Cow<'static, str> {
+        let name = match self {
+            Value::Int(_) => "Int",
+            Value::Float(_) => "Float",
+            Value::String(_) => "String",
+            Value::Bool(_) => "Bool",
+            Value::None => "None",
+            Value::Function(_) | Value::Builtin(_) | Value::Constructor(..) => "Function",
+            Value::Module(_) => "Module",
+            Value::List(_) => "List",
+            Value::Map(_) => "Map",
+            Value::Struct(_) => "Struct",
+            Value::Instance(instance) => return instance.borrow().struct_.name.clone().into(),
+            Value::Enum(_) => "Enum",
+            Value::Variant(variant) => return variant.enum_.name.clone().into(),
+        };
+        Cow::Borrowed(name)
+    }
+
+    pub fn is_type(&self, type_: &Type) -> bool {
+        match (self, type_) {
+            (Value::Int(_), Type::Int | Type::Number)
+            | (Value::Float(_), Type::Float | Type::Number)
+            | (Value::String(_), Type::String)
+            | (Value::Bool(_), Type::Bool) => true,
+            (Value::Function(closure), Type::Function { params, .. }) => {
+                closure.function.params.len() == params.len()
+            }
+            (Value::Builtin(builtin), Type::Function { params, .. }) => {
+                builtin.params.len() == params.len()
+            }
+            (Value::Constructor(enum_, index), Type::Function { params, .. }) => {
+                enum_.variants[*index].types.len() == params.len()
+            }
+            (Value::List(values), Type::List(type_)) => {
+                values.borrow().iter().all(|value| value.is_type(type_))
+            }
+            (Value::Map(map), Type::Map(key_type, value_type)) => map
+                .borrow()
+                .entries()
+                .iter()
+                .all(|(key, value)| key.is_type(key_type) && value.is_type(value_type)),
+            (Value::Instance(instance), Type::Named(name)) => {
+                instance.borrow().struct_.name == *name
+            }
+            (Value::Variant(variant), Type::Named(name)) => variant.enum_.name == *name,
+            (Value::None, Type::Optional(_)) => true,
+            (value, Type::Optional(type_)) => value.is_type(type_),
+            _ => false,
+        }
+    }
+
+    pub fn default_for(type_: &Type) -> Option<Self> {
+        match type_ {
+            Type::String => Some(Value::String(String::new())),
+            Type::Int | Type::Number => Some(Value::Int(0)),
+            Type::Float => Some(Value::Float(0.0)),
+            Type::Bool => Some(Value::Bool(false)),
+            Type::Function { .. } | Type::Named(_) => None,
+            Type::Optional(_) => Some(Value::None),
+            Type::List(_) => Some(Value::list(Vec::new())),
+            Type::Map(..) => Some(Value::map(Map::default())),
+        }
+    }
+
+    pub fn list(values: Vec<Value>) -> Self {
+        Value::List(Rc::new(RefCell::new(values)))
+    }
+
+    pub fn map(map: Map) -> Self {
+        Value::Map(Rc::new(RefCell::new(map)))
+    }
+
+    /// The value of an int or float as a float.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Int(int) => Some(*int as f64),
+            Value::Float(float) => Some(*float),
+            _ => None,
+        }
+    }
+
+    /// Formats the value the way it would be written in code, with strings
+    /// quoted.
+    pub fn repr(&self) -> String {
+        Repr(self).to_string()
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Int(left), Value::Int(right)) => left == right,
+            (Value::Float(left), Value::Float(right)) => left == right,
+            (Value::Int(int), Value::Float(float)) | (Value::Float(float), Value::Int(int)) => {
+                float_to_int(*float) == Some(*int)
+            }
+            (Value::String(left), Value::String(right)) => left == right,
+            (Value::Bool(left), Value::Bool(right)) => left == right,
+            (Value::None, Value::None) => true,
+            (Value::Function(left), Value::Function(right)) => Rc::ptr_eq(left, right),
+            (Value::Builtin(left), Value::Builtin(right)) => left.name == right.name,
+            (Value::Module(left), Value::Module(right)) => left.name == right.name,
+            (Value::List(left), Value::List(right)) => {
+                compare(left, right, |left, right| left == right)
+            }
+            (Value::Map(left), Value::Map(right)) => {
+                compare(left, right, |left, right| left == right)
+            }
+            (Value::Struct(left), Value::Struct(right)) => Rc::ptr_eq(left, right),
+            (Value::Instance(left), Value::Instance(right)) => {
+                compare(left, right, |left, right| {
+                    Rc::ptr_eq(&left.struct_, &right.struct_) && left.fields == right.fields
+                })
+            }
+            (Value::Enum(left), Value::Enum(right)) => Rc::ptr_eq(left, right),
+            (Value::Constructor(left, left_index), Value::Constructor(right, right_index)) => {
+                Rc::ptr_eq(left, right) && left_index == right_index
+            }
+            (Value::Variant(left), Value::Variant(right)) => {
+                Rc::ptr_eq(&left.enum_, &right.enum_)
+                    && left.index == right.index
+                    && left.values == right.values
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Compares the values `left` and `right` point to with `eq`, unless they are
+/// the same value, or `left` and `right` are already being compared further
+/// out. Either way they are equal if nothing else tells them apart.
+fn compare<T>(left: &Rc<RefCell<T>>, right: &Rc<RefCell<T>>, eq: impl Fn(&T, &T) -> bool) -> bool {
+    if Rc::ptr_eq(left, right) {
+        return true;
+    }
+    let pair = (
+        Rc::as_ptr(left) as *const (),
+        Rc::as_ptr(right) as *const (),
+    );
+    match Visit::enter(&COMPARING, pair) {
+        Some(_visit) => eq(&left.borrow(), &right.borrow()),
+        None => true,
+    }
+}
+
+/// Displays a value like `Value::repr`, without building the strings of the
+/// values it contains one inside the other.
+struct Repr<'a>(&'a Value);
+
+impl fmt::Display for Repr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Value::String(string) => write!(f, "{:?}", string),
+            value => write!(f, "{}", value),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(int) => write!(f, "{}", int),
+            // Always with a `.` or exponent, such as `1.0` or `1e21`, so
+            // floats can be told apart from ints
+            Value::Float(float) => write!(f, "{:?}", float),
+            Value::String(string) => write!(f, "{}", string),
+            Value::Bool(bool) => write!(f, "{}", bool),
+            Value::None => write!(f, "none"),
+            Value::Function(closure) => write!(f, "<fn {}>", closure.name()),
+            Value::Builtin(builtin) => write!(f, "<builtin {}>", builtin.name),
+            Value::Module(module) => write!(f, "<module {}>", module.name),
+            Value::List(values) => {
+                let Some(_visit) = Visit::enter(&DISPLAYING, Rc::as_ptr(values) as *const ())
+                else {
+                    return write!(f, "[...]");
+                };
+                write!(f, "[")?;
+                for (i, value) in values.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", Repr(value))?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(map) => {
+                let Some(_visit) = Visit::enter(&DISPLAYING, Rc::as_ptr(map) as *const ()) else {
+                    return write!(f, "{{...}}");
+                };
+                write!(f, "{{")?;
+                for (i, (key, value)) in map.borrow().entries().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", Repr(key), Repr(value))?;
+                }
+                write!(f, "}}")
+            }
+            Value::Struct(struct_) => write!(f, "<struct {}>", struct_.name),
+            Value::Instance(instance) => {
+                let visit = Visit::enter(&DISPLAYING, Rc::as_ptr(instance) as *const ());
+                let instance = instance.borrow();
+                if visit.is_none() {
+                    return write!(f, "{} {{ ... }}", instance.struct_.name);
+                }
+                write!(f, "{} {{ ", instance.struct_.name)?;
+                for (i, (field, value)) in instance
+                    .struct_
+                    .fields
+                    .iter()
+                    .zip(&instance.fields)
+                    .enumerate()
+                {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", field.name, Repr(value))?;
+                }
+                write!(f, " }}")
+            }
+            Value::Enum(enum_) => write!(f, "<enum {}>", enum_.name),
+            Value::Constructor(enum_, index) => {
+                write!(f, "<variant {}>", enum_.variants[*index].name)
+            }
+            Value::Variant(variant) => {
+                write!(f, "{}", variant.name())?;
+                if variant.values.is_empty() {
+                    return Ok(());
+                }
+                write!(f, "(")?;
+                for (i, value) in variant.values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", Repr(value))?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// The int equal to `float`, if it is a whole number in the range of ints.
+pub fn float_to_int(float: f64) -> Option<i64> {
+    // `i64::MAX` rounds up to 2^63 as a float, which is just out of range
+    let in_range = float >= i64::MIN as f64 && float < i64::MAX as f64;
+    (float.fract() == 0.0 && in_range).then_some(float as i64)
+}